@@ -1,18 +1,86 @@
-use soroban_sdk::{Address, Env};
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::{Address, Bytes, BytesN, Env};
 
-use crate::storage_types::DataKey;
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, Role};
+
+const ENVELOPE_TYPE_CONTRACT_ID: u32 = 8;
+const CONTRACT_ID_PREIMAGE_FROM_ADDRESS: u32 = 0;
 
 pub fn has_administrator(e: &Env) -> bool {
     let key = DataKey::Admin;
     e.storage().instance().has(&key)
 }
 
-pub fn read_administrator(e: &Env) -> Address {
+pub fn read_administrator(e: &Env) -> Result<Address, SaleError> {
     let key = DataKey::Admin;
-    e.storage().instance().get(&key).unwrap()
+    e.storage()
+        .instance()
+        .get(&key)
+        .ok_or(SaleError::AdminNotSet)
 }
 
 pub fn write_administrator(e: &Env, id: &Address) {
     let key = DataKey::Admin;
     e.storage().instance().set(&key, id);
 }
+
+//A contract address is the hash of the network id, its deployer and the salt, so only
+//the deployer can give a salt that leads back to this contract
+pub fn is_deployer(e: &Env, deployer: &Address, salt: &BytesN<32>) -> bool {
+    let mut preimage = Bytes::new(e);
+    preimage.extend_from_array(&ENVELOPE_TYPE_CONTRACT_ID.to_be_bytes());
+    preimage.append(&e.ledger().network_id().into());
+    preimage.extend_from_array(&CONTRACT_ID_PREIMAGE_FROM_ADDRESS.to_be_bytes());
+    //the xdr of an address value starts with the ScVal type
+    preimage.append(&deployer.clone().to_xdr(e).slice(4..));
+    preimage.append(&salt.clone().into());
+    let contract_id: Bytes = e.crypto().sha256(&preimage).to_bytes().into();
+
+    //followed by the contract ScAddress type before the contract id
+    e.current_contract_address().to_xdr(e).slice(8..) == contract_id
+}
+
+pub fn read_pending_administrator(e: &Env) -> Option<Address> {
+    let key = DataKey::PendingAdmin;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_pending_administrator(e: &Env, id: Option<Address>) {
+    let key = DataKey::PendingAdmin;
+    match id {
+        Some(id) => e.storage().instance().set(&key, &id),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//The admin is the owner and holds every role, other roles are granted per address
+pub fn has_role(e: &Env, role: Role, account: &Address) -> bool {
+    if let Ok(admin) = read_administrator(e) {
+        if admin == *account {
+            return true;
+        }
+    }
+    if role == Role::Owner {
+        return false;
+    }
+    let key = DataKey::Role(role, account.clone());
+    e.storage().instance().has(&key)
+}
+
+pub fn write_role(e: &Env, role: Role, account: &Address, granted: bool) {
+    let key = DataKey::Role(role, account.clone());
+    if granted {
+        e.storage().instance().set(&key, &true);
+    } else {
+        e.storage().instance().remove(&key);
+    }
+}
+
+pub fn require_role(e: &Env, caller: &Address, role: Role) -> Result<(), SaleError> {
+    caller.require_auth();
+    if !has_role(e, role, caller) {
+        return Err(SaleError::Unauthorized);
+    }
+    Ok(())
+}
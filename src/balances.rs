@@ -1,5 +1,7 @@
 use crate::{
-    sale_details::read_sales_parameters,
+    errors::SaleError,
+    release::read_halt_refunded,
+    sale_details::{read_oversubscription, read_sales_parameters},
     storage_types::{DataKey, BUMP_AMOUNT, LIFETIME_THRESHOLD},
 };
 use soroban_sdk::{Address, Env};
@@ -50,6 +52,14 @@ pub fn update_make_contribution_amount(
     write_total_contribution(e, payment_token, contribution);
 }
 
+pub fn clear_participant_contribution_amount(
+    e: &Env,
+    participant: Address,
+    payment_token: Address,
+) {
+    write_participant_contribution_amount(e, participant, payment_token, 0);
+}
+
 pub fn read_participant_purchase_amount(e: &Env, addr: Address) -> i128 {
     let key = DataKey::AmountPurchased(addr);
     if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
@@ -70,17 +80,153 @@ fn write_participant_purchase_amount(e: &Env, addr: Address, amount: i128) {
         .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
 }
 
-pub fn update_participant_purchase_amount(e: &Env, addr: Address, amount_purchased: i128) {
+pub fn update_participant_purchase_amount(
+    e: &Env,
+    addr: Address,
+    amount_purchased: i128,
+) -> Result<(), SaleError> {
     let pre_purchase_amount = read_participant_purchase_amount(e, addr.clone());
     let max_buy = read_sales_parameters(e).max_buy as i128;
     let total_purchased = pre_purchase_amount + amount_purchased;
     if total_purchased > max_buy {
-        panic!("this put the total amount purchased above the max buy limit")
+        return Err(SaleError::MaxBuyExceeded);
     }
     write_participant_purchase_amount(e, addr, total_purchased);
     if pre_purchase_amount == 0 {
         write_participants_count(e, 1);
     }
+    Ok(())
+}
+
+//Lower a participant's purchase when it opts out, it stops counting as a participant once
+//nothing is left
+pub fn reduce_participant_purchase_amount(e: &Env, addr: Address, amount: i128) {
+    let total_purchased = read_participant_purchase_amount(e, addr.clone()) - amount;
+    write_participant_purchase_amount(e, addr, total_purchased);
+    if total_purchased == 0 {
+        write_participants_count(e, -1);
+    }
+}
+
+//Part of a participant's purchase paid with a payment token
+pub fn read_participant_token_purchase(
+    e: &Env,
+    participant: Address,
+    payment_token: Address,
+) -> i128 {
+    let key = DataKey::TokenPurchase(participant, payment_token);
+    if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn write_participant_token_purchase(
+    e: &Env,
+    participant: Address,
+    payment_token: Address,
+    amount: i128,
+) {
+    let key = DataKey::TokenPurchase(participant, payment_token);
+    e.storage().persistent().set(&key, &amount);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+pub fn read_participant_claimed_amount(e: &Env, addr: Address) -> i128 {
+    let key = DataKey::AmountClaimed(addr);
+    if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn update_participant_claimed_amount(e: &Env, addr: Address, amount_claimed: i128) {
+    let key = DataKey::AmountClaimed(addr.clone());
+    let total_claimed = read_participant_claimed_amount(e, addr) + amount_claimed;
+    e.storage().persistent().set(&key, &total_claimed);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+//Filled share of the demand as (hard_cap, total_sold), when the sale is oversubscribed
+fn read_fill_ratio(e: &Env) -> Option<(i128, i128)> {
+    let hard_cap = read_sales_parameters(e).hard_cap as i128;
+    let total_sold = read_total_sold(e);
+    if read_oversubscription(e) && total_sold > hard_cap {
+        Some((hard_cap, total_sold))
+    } else {
+        None
+    }
+}
+
+//Tokens actually allocated to a participant, pro-rata to the purchase when oversubscribed
+//and cut down to what it kept after a halt refund
+pub fn read_participant_allocation(e: &Env, addr: Address) -> i128 {
+    if let Some(kept) = read_halt_refunded(e, addr.clone()) {
+        return kept;
+    }
+    let purchased = read_participant_purchase_amount(e, addr);
+    match read_fill_ratio(e) {
+        Some((hard_cap, total_sold)) => purchased * hard_cap / total_sold,
+        None => purchased,
+    }
+}
+
+//Unfilled part of a participant's contribution, rounded down so the refunds never
+//exceed what is left once the project withdraws its share
+pub fn read_participant_excess_contribution(
+    e: &Env,
+    participant: Address,
+    payment_token: Address,
+) -> i128 {
+    match read_fill_ratio(e) {
+        Some((hard_cap, total_sold)) => {
+            let contribution = read_participant_contribution_amount(e, participant, payment_token);
+            let used = (contribution * hard_cap + total_sold - 1) / total_sold;
+            contribution - used
+        }
+        None => 0,
+    }
+}
+
+pub fn read_excess_refunded(e: &Env, participant: Address) -> bool {
+    let key = DataKey::ExcessRefunded(participant);
+    if let Some(refunded) = e.storage().persistent().get::<DataKey, bool>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        refunded
+    } else {
+        false
+    }
+}
+
+pub fn write_excess_refunded(e: &Env, participant: Address) {
+    let key = DataKey::ExcessRefunded(participant);
+    e.storage().persistent().set(&key, &true);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+//Funds raised in a payment token that the project keeps, the filled share when oversubscribed
+pub fn read_raised_amount(e: &Env, token_address: Address) -> i128 {
+    let total_contribution = read_total_contribution(e, token_address);
+    match read_fill_ratio(e) {
+        Some((hard_cap, total_sold)) => total_contribution * hard_cap / total_sold,
+        None => total_contribution,
+    }
 }
 
 pub fn read_total_contribution(e: &Env, token_address: Address) -> i128 {
@@ -127,6 +273,26 @@ pub fn write_total_sold(e: &Env, amount_sold: i128) {
         .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
 }
 
+pub fn read_tokens_remaining(e: &Env) -> i128 {
+    let key = DataKey::TokensRemaining;
+    if let Some(remaining) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        remaining
+    } else {
+        0
+    }
+}
+
+pub fn write_tokens_remaining(e: &Env, amount: i128) {
+    let key = DataKey::TokensRemaining;
+    e.storage().persistent().set(&key, &amount);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
 pub fn read_participants_count(e: &Env) -> i128 {
     let key = DataKey::ParticipantsCount;
     if let Some(count) = e.storage().persistent().get::<DataKey, i128>(&key) {
@@ -1,59 +1,260 @@
-use crate::access::{has_administrator, read_administrator, write_administrator};
+use crate::access::{
+    has_administrator, has_role, is_deployer, read_administrator, read_pending_administrator,
+    require_role, write_administrator, write_pending_administrator, write_role,
+};
 use crate::balances::{
-    read_participant_contribution_amount, read_participant_purchase_amount,
-    read_total_contribution, read_total_sold, update_make_contribution_amount,
-    update_participant_purchase_amount, write_total_contribution, write_total_sold,
+    clear_participant_contribution_amount, read_excess_refunded, read_participant_allocation,
+    read_participant_claimed_amount, read_participant_contribution_amount,
+    read_participant_excess_contribution, read_participant_purchase_amount,
+    read_participant_token_purchase, read_raised_amount, read_tokens_remaining,
+    read_total_contribution, read_total_sold, reduce_participant_purchase_amount,
+    update_make_contribution_amount, update_participant_claimed_amount,
+    update_participant_purchase_amount, write_excess_refunded, write_participant_token_purchase,
+    write_tokens_remaining, write_total_contribution, write_total_sold,
+};
+use crate::errors::SaleError;
+use crate::events::{
+    self, AdminTransferEvent, ClaimEvent, ContributionEvent, FeeCollectedEvent,
+    FundRecipientsSetEvent, HaltVoteEvent, InitializedEvent, KycSignerSetEvent,
+    LiquidityAddedEvent, LiquiditySetEvent, LockEvent, OptOutEvent, OptOutSetEvent, OracleSetEvent,
+    OversubscriptionSetEvent, PartialFillSetEvent, PauseEvent, PaymentTokenConfigEvent,
+    PaymentTokenSetEvent, PlatformFeeSetEvent, ProjectWalletSetEvent, RefundEvent,
+    ReleaseHaltedEvent, ReleaseSetEvent, RoleEvent, SaleCancelledEvent, SaleFinalizedEvent,
+    SaleParametersSetEvent, SaleTokenSetEvent, SwapRateSetEvent, TokensForfeitedEvent, UnlockEvent,
+    WhitelistSetEvent, WithdrawalEvent,
+};
+use crate::fees::{
+    read_fee_collected, read_payment_fee_amount, read_platform_fee, read_sale_token_fee_amount,
+    read_sale_token_fee_deposit, write_fee_collected, write_platform_fee,
 };
+use crate::funds::{
+    read_distributable_funds, read_fund_recipients, read_withdrawable_funds, read_withdrawn_funds,
+    update_withdrawn_funds, write_fund_recipients,
+};
+use crate::kyc::{read_kyc_signer, write_kyc_signer, write_nonce_consumed};
+use crate::liquidity::{
+    read_liquidity_config, read_liquidity_provided, read_liquidity_reserve, write_liquidity_config,
+    write_liquidity_provided, LiquidityRouterClient,
+};
+use crate::locks::{add_to_lock, read_token_lock, write_token_lock};
+use crate::opt_out::{read_opt_out_config, write_opt_out_config};
+use crate::oracle::{read_oracle_config, write_oracle_config};
+use crate::pause::{check_not_paused, read_is_paused, write_paused};
 use crate::payment_tokens::{
-    read_active_payment_tokens, read_is_supported_payment_token, read_payment_tokens,
-    write_payment_token,
+    read_active_payment_tokens, read_payment_token_config, read_payment_tokens,
+    remove_payment_token, write_payment_token, write_payment_token_config,
+};
+use crate::rates::{conversion_rate, read_conversion_rate, read_sale_rate, write_sales_rate};
+use crate::release::{
+    read_halt_refunded, read_halt_vote, read_halt_votes, read_halted_release,
+    read_release_schedule, read_released_bps, write_halt_refunded, write_halt_vote,
+    write_halt_votes, write_halted_release, write_release_schedule,
 };
-use crate::rates::{read_sale_rate, write_sales_rate};
 use crate::sale_details::{
-    read_fund_recipient, read_sales_parameters, write_fund_recipient, write_sales_parameters,
+    read_oversubscription, read_partial_fill, read_project_wallet, read_sale_outcome,
+    read_sale_status, read_sales_parameters, write_oversubscription, write_partial_fill,
+    write_project_wallet, write_sale_status, write_sales_parameters,
 };
 use crate::sale_token::{read_token, send_token, take_token, write_token};
-use crate::storage_types::SalesParameter;
+use crate::storage_types::{
+    ContributionPreview, FundRecipient, LiquidityConfig, OptOutConfig, OracleConfig,
+    PausableOperation, PaymentTokenConfig, PlatformFee, ReleaseSchedule, Role, SaleConfig,
+    SaleStatus, SalesParameter, SignedVoucher, SwapRate, TokenLock, WhitelistProof,
+};
+use crate::vesting::{read_claimable_amount, MAX_BPS};
+use crate::whitelist::{check_eligibility, read_merkle_root, write_merkle_root};
 
-use soroban_sdk::{contract, contractimpl, Address, Env, Vec};
+use soroban_sdk::{contract, contractimpl, symbol_short, token, vec, Address, BytesN, Env, Vec};
 
 pub trait SaleTrait {
-    fn initialize(e: Env, admin: Address);
-    fn set_sale_token(e: Env, token_address: Address);
-    fn set_payment_token(e: Env, payment_token: Address);
-    fn set_sale_parameters(
-        e: &Env,
-        start_time: u64,
-        end_time: u64,
-        soft_cap: u64,
-        hard_cap: u64,
-        min_buy: u64,
-        max_buy: u64,
-        tge_time: u64,
-    );
+    fn initialize(
+        e: Env,
+        admin: Address,
+        salt: BytesN<32>,
+        config: SaleConfig,
+        platform_fee: Option<PlatformFee>,
+        liquidity: Option<LiquidityConfig>,
+        release: Option<ReleaseSchedule>,
+    ) -> Result<(), SaleError>;
+    fn propose_admin(e: Env, new_admin: Address) -> Result<(), SaleError>;
+    fn accept_admin(e: Env) -> Result<(), SaleError>;
+    fn cancel_admin_transfer(e: Env) -> Result<(), SaleError>;
+    fn grant_role(e: Env, role: Role, account: Address) -> Result<(), SaleError>;
+    fn revoke_role(e: Env, role: Role, account: Address) -> Result<(), SaleError>;
+    fn renounce_role(e: Env, role: Role, account: Address) -> Result<(), SaleError>;
+    fn set_payment_token(e: Env, caller: Address, payment_token: Address) -> Result<(), SaleError>;
+    fn disable_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError>;
+    fn enable_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError>;
+    fn remove_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError>;
+    fn set_payment_token_limits(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+        raise_cap: i128,
+        min_contribution: i128,
+        max_contribution: i128,
+    ) -> Result<(), SaleError>;
+
+    fn set_swap_rate(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+        rate: SwapRate,
+    ) -> Result<(), SaleError>;
+    fn set_price_oracle(
+        e: Env,
+        caller: Address,
+        config: Option<OracleConfig>,
+    ) -> Result<(), SaleError>;
+    fn set_fund_recipient(e: Env, caller: Address, recipient: Address) -> Result<(), SaleError>;
+    fn set_fund_recipients(
+        e: Env,
+        caller: Address,
+        recipients: Vec<FundRecipient>,
+    ) -> Result<(), SaleError>;
+    fn set_project_wallet(e: Env, caller: Address, wallet: Address) -> Result<(), SaleError>;
+    fn set_partial_fill(e: Env, caller: Address, enabled: bool) -> Result<(), SaleError>;
+    fn set_opt_out_config(
+        e: Env,
+        caller: Address,
+        config: Option<OptOutConfig>,
+    ) -> Result<(), SaleError>;
+    fn set_oversubscription(e: Env, caller: Address, enabled: bool) -> Result<(), SaleError>;
+    fn set_merkle_root(e: Env, caller: Address, root: Option<BytesN<32>>) -> Result<(), SaleError>;
+    fn set_kyc_signer(e: Env, caller: Address, signer: Option<BytesN<32>>)
+        -> Result<(), SaleError>;
+    fn cancel_sale(e: Env, caller: Address) -> Result<(), SaleError>;
+    fn pause(e: Env, caller: Address, operation: PausableOperation) -> Result<(), SaleError>;
+    fn unpause(e: Env, caller: Address, operation: PausableOperation) -> Result<(), SaleError>;
 
-    fn set_swap_rate(e: Env, payment_token: Address, rate: u64);
-    fn set_fund_recipient(e: Env, recipient: Address);
-
-    fn contribute(e: Env, participant: Address, payment_token: Address, amount: i128);
-    fn claim_purchased_tokens(e: Env, participant: Address);
-    fn claim_refund(e: Env, participant: Address);
-    fn withdraw_raised_funds(e: Env);
-
-    fn get_sale_token(e: Env) -> Address;
-    fn get_payment_options(e: Env) -> Vec<Address>;
-    fn get_sale_rate(e: Env, payment_token: Address) -> u64;
-    fn get_payment_purchases(e: Env,participant: Address, payment_token: Address) -> i128 ;
-    fn get_sales_parameters(e: &Env) -> SalesParameter;
-    fn get_participant_total_purchase(e: Env, participant: Address) -> i128;
-    fn get_participant_contribution(e: Env, participant: Address, payment_token: Address) -> i128;
-
-    fn get_total_sold(e: Env) -> i128;
-    fn get_total_contribution(e: Env, payment_token: Address) -> i128;
-    fn get_fund_recipient(e: Env) -> Address;
-    fn get_admin(e: &Env) -> Address;
-    fn get_supported_tokens(e: Env) -> Vec<Address>;
-    fn get_current_timestamp(e: Env) -> u64;
+    fn contribute(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<(), SaleError>;
+    #[allow(clippy::too_many_arguments)]
+    fn contribute_with_min_out(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        min_tokens_out: i128,
+        deadline: u64,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<i128, SaleError>;
+    fn preview_contribute(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<ContributionPreview, SaleError>;
+    fn opt_out(e: Env, participant: Address, payment_token: Address) -> Result<(), SaleError>;
+    fn finalize_sale(e: Env) -> Result<(), SaleError>;
+    fn lock_tokens(
+        e: Env,
+        from: Address,
+        token: Address,
+        amount: i128,
+        unlock_time: u64,
+    ) -> Result<(), SaleError>;
+    fn extend_lock(
+        e: Env,
+        caller: Address,
+        token: Address,
+        unlock_time: u64,
+    ) -> Result<(), SaleError>;
+    fn withdraw_locked(
+        e: Env,
+        caller: Address,
+        token: Address,
+        recipient: Address,
+    ) -> Result<(), SaleError>;
+    fn claim_purchased_tokens(e: Env, participant: Address) -> Result<(), SaleError>;
+    fn claim(e: Env, participant: Address) -> Result<(), SaleError>;
+    fn claim_refund(e: Env, participant: Address) -> Result<(), SaleError>;
+    fn withdraw_raised_funds(e: Env, recipient: Address) -> Result<(), SaleError>;
+    fn vote_halt(e: Env, participant: Address) -> Result<(), SaleError>;
+    fn claim_halt_refund(e: Env, participant: Address) -> Result<(), SaleError>;
+
+    fn get_sale_token(e: Env) -> Result<Address, SaleError>;
+    fn get_payment_options(e: Env) -> Result<Vec<Address>, SaleError>;
+    fn get_sale_rate(e: Env, payment_token: Address) -> Result<Option<SwapRate>, SaleError>;
+    fn get_payment_purchases(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError>;
+    fn get_sales_parameters(e: &Env) -> Result<SalesParameter, SaleError>;
+    fn get_sale_status(e: Env) -> Result<SaleStatus, SaleError>;
+    fn get_participant_total_purchase(e: Env, participant: Address) -> Result<i128, SaleError>;
+    fn get_participant_allocation(e: Env, participant: Address) -> Result<i128, SaleError>;
+    fn get_claimable_amount(e: Env, participant: Address) -> Result<i128, SaleError>;
+    fn get_claimed_amount(e: Env, participant: Address) -> Result<i128, SaleError>;
+    fn get_participant_contribution(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError>;
+
+    fn get_total_sold(e: Env) -> Result<i128, SaleError>;
+    fn get_total_contribution(e: Env, payment_token: Address) -> Result<i128, SaleError>;
+    fn get_fund_recipient(e: Env) -> Result<Address, SaleError>;
+    fn get_fund_recipients(e: Env) -> Result<Vec<FundRecipient>, SaleError>;
+    fn get_platform_fee(e: Env) -> Result<Option<PlatformFee>, SaleError>;
+    fn get_fee_collected(e: Env, token: Address) -> Result<i128, SaleError>;
+    fn get_liquidity_config(e: Env) -> Result<Option<LiquidityConfig>, SaleError>;
+    fn get_lock(e: Env, token: Address) -> Result<Option<TokenLock>, SaleError>;
+    fn get_release_schedule(e: Env) -> Result<Option<ReleaseSchedule>, SaleError>;
+    fn get_opt_out_config(e: Env) -> Result<Option<OptOutConfig>, SaleError>;
+    fn get_released_bps(e: Env) -> Result<u32, SaleError>;
+    fn get_halt_votes(e: Env) -> Result<i128, SaleError>;
+    fn get_halt_vote(e: Env, participant: Address) -> Result<i128, SaleError>;
+    fn get_liquidity_provided(e: Env, payment_token: Address) -> Result<i128, SaleError>;
+    fn get_withdrawable_funds(
+        e: Env,
+        recipient: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError>;
+    fn get_withdrawn_funds(
+        e: Env,
+        recipient: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError>;
+    fn get_project_wallet(e: Env) -> Result<Address, SaleError>;
+    fn get_tokens_remaining(e: Env) -> Result<i128, SaleError>;
+    fn get_partial_fill(e: Env) -> Result<bool, SaleError>;
+    fn get_oversubscription(e: Env) -> Result<bool, SaleError>;
+    fn get_price_oracle(e: Env) -> Result<Option<OracleConfig>, SaleError>;
+    fn get_merkle_root(e: Env) -> Result<Option<BytesN<32>>, SaleError>;
+    fn get_kyc_signer(e: Env) -> Result<Option<BytesN<32>>, SaleError>;
+    fn get_admin(e: &Env) -> Result<Address, SaleError>;
+    fn get_pending_admin(e: Env) -> Result<Option<Address>, SaleError>;
+    fn has_role(e: Env, role: Role, account: Address) -> Result<bool, SaleError>;
+    fn get_supported_tokens(e: Env) -> Result<Vec<Address>, SaleError>;
+    fn get_payment_token_config(
+        e: Env,
+        payment_token: Address,
+    ) -> Result<Option<PaymentTokenConfig>, SaleError>;
+    fn get_current_timestamp(e: Env) -> Result<u64, SaleError>;
+    fn is_paused(e: Env, operation: PausableOperation) -> Result<bool, SaleError>;
 }
 
 #[contract]
@@ -61,218 +262,1712 @@ pub struct TokenSale;
 
 #[contractimpl]
 impl SaleTrait for TokenSale {
-    fn initialize(e: Env, admin: Address) {
+    //Sets up the whole sale in one call authorized by the admin, who deposits the hard cap
+    //of sale tokens. The admin must be the account that deployed the contract, proven with
+    //the deployment salt, so nobody else can initialize it first. The sale token and
+    //parameters cannot change afterwards
+
+    fn initialize(
+        e: Env,
+        admin: Address,
+        salt: BytesN<32>,
+        config: SaleConfig,
+        platform_fee: Option<PlatformFee>,
+        liquidity: Option<LiquidityConfig>,
+        release: Option<ReleaseSchedule>,
+    ) -> Result<(), SaleError> {
         if has_administrator(&e) {
-            panic!("already has an admin")
+            return Err(SaleError::AlreadyInitialized);
         }
+        admin.require_auth();
+        if !is_deployer(&e, &admin, &salt) {
+            return Err(SaleError::Unauthorized);
+        }
+
+        if config.payment_options.is_empty() {
+            return Err(SaleError::InvalidPaymentOptions);
+        }
+        for (index, option) in config.payment_options.iter().enumerate() {
+            let duplicated = config
+                .payment_options
+                .iter()
+                .skip(index + 1)
+                .any(|other| other.token == option.token);
+            if option.rate.numerator == 0
+                || option.rate.denominator == 0
+                || option.token == config.sale_token
+                || duplicated
+            {
+                return Err(SaleError::InvalidPaymentOptions);
+            }
+        }
+
         write_administrator(&e, &admin);
+        events::publish(
+            &e,
+            symbol_short!("init"),
+            InitializedEvent {
+                admin: admin.clone(),
+            },
+        );
+
+        write_token(&e, &config.sale_token);
+        events::publish(
+            &e,
+            symbol_short!("sale_tkn"),
+            SaleTokenSetEvent {
+                token: config.sale_token,
+            },
+        );
+
+        for option in config.payment_options.iter() {
+            write_payment_token(&e, option.token.clone())?;
+            write_sales_rate(&e, option.token.clone(), option.rate);
+            events::publish(
+                &e,
+                symbol_short!("pay_tkn"),
+                PaymentTokenSetEvent {
+                    payment_token: option.token.clone(),
+                },
+            );
+            events::publish(
+                &e,
+                symbol_short!("rate"),
+                SwapRateSetEvent {
+                    payment_token: option.token,
+                    rate: option.rate,
+                },
+            );
+        }
+
+        update_fund_recipients(&e, single_fund_recipient(&e, config.fund_recipient))?;
+        if let Some(fee) = platform_fee {
+            update_platform_fee(&e, fee)?;
+        }
+        if let Some(liquidity) = liquidity {
+            update_liquidity_config(&e, liquidity)?;
+        }
+        if let Some(release) = release {
+            update_release_schedule(&e, release)?;
+        }
+
+        configure_sale(&e, &admin, config.parameters)
     }
 
-    fn set_payment_token(e: Env, payment_token: Address) {
-        let admin = read_administrator(&e);
+    //The admin is changed in two steps so a wrong address cannot take over the contract:
+    //the current admin proposes, then the new admin accepts with its own authorization
+
+    fn propose_admin(e: Env, new_admin: Address) -> Result<(), SaleError> {
+        let admin = read_administrator(&e)?;
         admin.require_auth();
-        write_payment_token(&e, payment_token);
+        write_pending_administrator(&e, Some(new_admin.clone()));
+        events::publish(
+            &e,
+            symbol_short!("adm_prop"),
+            AdminTransferEvent { admin, new_admin },
+        );
+        Ok(())
     }
 
-    fn set_sale_token(e: Env, token_address: Address) {
-        let admin = read_administrator(&e);
+    fn accept_admin(e: Env) -> Result<(), SaleError> {
+        let new_admin = read_pending_administrator(&e).ok_or(SaleError::NoPendingAdmin)?;
+        new_admin.require_auth();
+        let admin = read_administrator(&e)?;
+        write_administrator(&e, &new_admin);
+        write_pending_administrator(&e, None);
+        events::publish(
+            &e,
+            symbol_short!("admin_set"),
+            AdminTransferEvent { admin, new_admin },
+        );
+        Ok(())
+    }
+
+    fn cancel_admin_transfer(e: Env) -> Result<(), SaleError> {
+        let admin = read_administrator(&e)?;
         admin.require_auth();
-        write_token(&e, &token_address)
-    }
-
-    fn set_sale_parameters(
-        e: &Env,
-        start_time: u64,
-        end_time: u64,
-        soft_cap: u64,
-        hard_cap: u64,
-        min_buy: u64,
-        max_buy: u64,
-        tge_time: u64,
-    ) {
-        let admin = read_administrator(&e);
+        let new_admin = read_pending_administrator(&e).ok_or(SaleError::NoPendingAdmin)?;
+        write_pending_administrator(&e, None);
+        events::publish(
+            &e,
+            symbol_short!("adm_cncl"),
+            AdminTransferEvent { admin, new_admin },
+        );
+        Ok(())
+    }
+
+    fn grant_role(e: Env, role: Role, account: Address) -> Result<(), SaleError> {
+        let admin = read_administrator(&e)?;
         admin.require_auth();
+        if role == Role::Owner {
+            return Err(SaleError::InvalidRole);
+        }
+        write_role(&e, role, &account, true);
+        events::publish(&e, symbol_short!("grant"), RoleEvent { role, account });
+        Ok(())
+    }
 
-        write_sales_parameters(
-            e, start_time, end_time, soft_cap, hard_cap, min_buy, max_buy, tge_time,
+    fn revoke_role(e: Env, role: Role, account: Address) -> Result<(), SaleError> {
+        let admin = read_administrator(&e)?;
+        admin.require_auth();
+        if role == Role::Owner {
+            return Err(SaleError::InvalidRole);
+        }
+        write_role(&e, role, &account, false);
+        events::publish(&e, symbol_short!("revoke"), RoleEvent { role, account });
+        Ok(())
+    }
+
+    fn renounce_role(e: Env, role: Role, account: Address) -> Result<(), SaleError> {
+        account.require_auth();
+        if role == Role::Owner {
+            return Err(SaleError::InvalidRole);
+        }
+        write_role(&e, role, &account, false);
+        events::publish(&e, symbol_short!("revoke"), RoleEvent { role, account });
+        Ok(())
+    }
+
+    fn set_payment_token(e: Env, caller: Address, payment_token: Address) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        if payment_token == read_token(&e)? {
+            return Err(SaleError::InvalidPaymentOptions);
+        }
+        write_payment_token(&e, payment_token.clone())?;
+        events::publish(
+            &e,
+            symbol_short!("pay_tkn"),
+            PaymentTokenSetEvent { payment_token },
         );
+        Ok(())
+    }
 
-        let token_address = &read_token(&e);
-        take_token(e, token_address, &admin, hard_cap as i128);
+    //A disabled token stops accepting contributions but its balances stay refundable
+    //and withdrawable
+
+    fn disable_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        update_payment_token_config(&e, payment_token, |config| config.enabled = false)
     }
 
-    fn set_swap_rate(e: Env, payment_token: Address, rate: u64) {
-        let admin = read_administrator(&e);
-        admin.require_auth();
+    fn enable_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        update_payment_token_config(&e, payment_token, |config| config.enabled = true)
+    }
 
-        let is_supported = read_is_supported_payment_token(&e, payment_token.clone());
+    //Only a token nobody contributed with can be removed, so no balance is stranded
 
-        if !is_supported {
-            panic!("the token entered is not a supported payment option")
+    fn remove_payment_token(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        if read_total_contribution(&e, payment_token.clone()) > 0 {
+            return Err(SaleError::PaymentTokenInUse);
         }
+        remove_payment_token(&e, payment_token.clone())?;
+        events::publish(
+            &e,
+            symbol_short!("pay_rm"),
+            PaymentTokenSetEvent { payment_token },
+        );
+        Ok(())
+    }
 
-        write_sales_rate(&e, payment_token, rate);
+    fn set_payment_token_limits(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+        raise_cap: i128,
+        min_contribution: i128,
+        max_contribution: i128,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        if raise_cap < 0
+            || min_contribution < 0
+            || max_contribution < 0
+            || (max_contribution > 0 && max_contribution < min_contribution)
+        {
+            return Err(SaleError::InvalidPaymentOptions);
+        }
+        update_payment_token_config(&e, payment_token, |config| {
+            config.raise_cap = raise_cap;
+            config.min_contribution = min_contribution;
+            config.max_contribution = max_contribution;
+        })
     }
 
-    fn set_fund_recipient(e: Env, recipient: Address) {
-        let admin = read_administrator(&e);
-        admin.require_auth();
-        write_fund_recipient(&e, recipient);
+    fn set_swap_rate(
+        e: Env,
+        caller: Address,
+        payment_token: Address,
+        rate: SwapRate,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+
+        if rate.denominator == 0 {
+            return Err(SaleError::InvalidPaymentOptions);
+        }
+
+        if read_payment_token_config(&e, payment_token.clone()).is_none() {
+            return Err(SaleError::UnsupportedPaymentToken);
+        }
+
+        write_sales_rate(&e, payment_token.clone(), rate);
+        events::publish(
+            &e,
+            symbol_short!("rate"),
+            SwapRateSetEvent {
+                payment_token,
+                rate,
+            },
+        );
+        Ok(())
     }
 
-    fn contribute(e: Env, participant: Address, payment_token: Address, amount: i128) {
-        participant.require_auth();
-        let is_supported = read_is_supported_payment_token(&e, payment_token.clone());
+    //Price every payment token from a USD oracle instead of its fixed swap rate, removing
+    //the config goes back to the fixed rates
+
+    fn set_price_oracle(
+        e: Env,
+        caller: Address,
+        config: Option<OracleConfig>,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+
+        match config {
+            Some(config) => {
+                if config.sale_token_price <= 0
+                    || config.max_staleness == 0
+                    || config.max_deviation_bps > MAX_BPS
+                {
+                    return Err(SaleError::InvalidConfig);
+                }
+                write_oracle_config(&e, Some(config.clone()));
+                events::publish(&e, symbol_short!("oracle"), OracleSetEvent { config });
+            }
+            None => {
+                if let Some(config) = read_oracle_config(&e) {
+                    write_oracle_config(&e, None);
+                    events::publish(&e, symbol_short!("oracle_rm"), OracleSetEvent { config });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Send all the raised funds to a single recipient
+
+    fn set_fund_recipient(e: Env, caller: Address, recipient: Address) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Finance)?;
+        update_fund_recipients(&e, single_fund_recipient(&e, recipient))
+    }
+
+    //Split the raised funds between recipients by basis points, each one withdrawing its
+    //own share. The rounding dust goes to the first recipient
+
+    fn set_fund_recipients(
+        e: Env,
+        caller: Address,
+        recipients: Vec<FundRecipient>,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Finance)?;
+        update_fund_recipients(&e, recipients)
+    }
+
+    fn set_project_wallet(e: Env, caller: Address, wallet: Address) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Finance)?;
+        write_project_wallet(&e, wallet.clone());
+        events::publish(
+            &e,
+            symbol_short!("wallet"),
+            ProjectWalletSetEvent { wallet },
+        );
+        Ok(())
+    }
+
+    //When enabled, a contribution crossing the hard cap is filled up to the cap and the
+    //excess is never taken from the participant, otherwise it is rejected
+
+    fn set_partial_fill(e: Env, caller: Address, enabled: bool) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        match read_sale_status(&e) {
+            SaleStatus::NotConfigured | SaleStatus::Upcoming => {}
+            _ => return Err(SaleError::SaleAlreadyStarted),
+        }
+        write_partial_fill(&e, enabled);
+        events::publish(
+            &e,
+            symbol_short!("part_fill"),
+            PartialFillSetEvent { enabled },
+        );
+        Ok(())
+    }
+
+    //The opt-out terms can change until the sale starts
+
+    fn set_opt_out_config(
+        e: Env,
+        caller: Address,
+        config: Option<OptOutConfig>,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        match read_sale_status(&e) {
+            SaleStatus::NotConfigured | SaleStatus::Upcoming => {}
+            _ => return Err(SaleError::SaleAlreadyStarted),
+        }
+        update_opt_out_config(&e, config)
+    }
+
+    //When enabled, contributions are not limited by the hard cap during the sale and each
+    //participant is allocated hard_cap * purchase / total_sold, the rest being refunded
+
+    fn set_oversubscription(e: Env, caller: Address, enabled: bool) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+        match read_sale_status(&e) {
+            SaleStatus::NotConfigured | SaleStatus::Upcoming => {}
+            _ => return Err(SaleError::SaleAlreadyStarted),
+        }
+        write_oversubscription(&e, enabled);
+        events::publish(
+            &e,
+            symbol_short!("oversub"),
+            OversubscriptionSetEvent { enabled },
+        );
+        Ok(())
+    }
+
+    //Restrict contributions to the addresses of a merkle tree, see merkle.rs for the leaf
+    //format. Removing the root opens the sale to anyone again
+
+    fn set_merkle_root(e: Env, caller: Address, root: Option<BytesN<32>>) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Compliance)?;
+        match root {
+            Some(root) => {
+                write_merkle_root(&e, Some(root.clone()));
+                events::publish(&e, symbol_short!("whitelist"), WhitelistSetEvent { root });
+            }
+            None => {
+                if let Some(root) = read_merkle_root(&e) {
+                    write_merkle_root(&e, None);
+                    events::publish(&e, symbol_short!("wl_rm"), WhitelistSetEvent { root });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Register the ed25519 key whose vouchers admit participants alongside the merkle list,
+    //see kyc.rs for the signed message. Removing the key stops accepting vouchers
+
+    fn set_kyc_signer(
+        e: Env,
+        caller: Address,
+        signer: Option<BytesN<32>>,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Compliance)?;
+        match signer {
+            Some(signer) => {
+                write_kyc_signer(&e, Some(signer.clone()));
+                events::publish(&e, symbol_short!("kyc_sign"), KycSignerSetEvent { signer });
+            }
+            None => {
+                if let Some(signer) = read_kyc_signer(&e) {
+                    write_kyc_signer(&e, None);
+                    events::publish(&e, symbol_short!("kyc_rm"), KycSignerSetEvent { signer });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //Abort the sale before its outcome is settled, every contribution becomes refundable.
+    //Once the sale has ended it can only be cancelled if it missed the soft cap, so the
+    //participants of a successful sale keep their tokens
+
+    fn cancel_sale(e: Env, caller: Address) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::SaleOperator)?;
+
+        match read_sale_status(&e) {
+            SaleStatus::Upcoming | SaleStatus::Active => {}
+            SaleStatus::Ended if read_sale_outcome(&e) == SaleStatus::Failed => {}
+            SaleStatus::Ended => return Err(SaleError::SaleSucceeded),
+            SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+            _ => return Err(SaleError::InvalidStatusTransition),
+        }
+        write_sale_status(&e, SaleStatus::Cancelled)?;
+
+        let token_address = read_token(&e)?;
+        let deposited_tokens = read_tokens_deposited(&e);
+        send_token(
+            &e,
+            &token_address,
+            &read_project_wallet(&e)?,
+            deposited_tokens,
+        );
+        events::publish(
+            &e,
+            symbol_short!("cancel"),
+            SaleCancelledEvent {
+                tokens_returned: deposited_tokens,
+                total_sold: read_total_sold(&e),
+            },
+        );
+        Ok(())
+    }
+
+    fn pause(e: Env, caller: Address, operation: PausableOperation) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Pauser)?;
+        write_paused(&e, operation, true);
+        events::publish(&e, symbol_short!("pause"), PauseEvent { operation });
+        Ok(())
+    }
+
+    fn unpause(e: Env, caller: Address, operation: PausableOperation) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Pauser)?;
+        write_paused(&e, operation, false);
+        events::publish(&e, symbol_short!("unpause"), PauseEvent { operation });
+        Ok(())
+    }
+
+    fn contribute(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<(), SaleError> {
+        execute_contribution(
+            &e,
+            participant,
+            payment_token,
+            amount,
+            0,
+            whitelist_proof,
+            voucher,
+        )?;
+        Ok(())
+    }
+
+    //Same as contribute but fails instead of buying less than min_tokens_out, after the
+    //hard cap and partial fill are applied, or once the deadline has passed. Returns the
+    //tokens purchased
+
+    #[allow(clippy::too_many_arguments)]
+    fn contribute_with_min_out(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        min_tokens_out: i128,
+        deadline: u64,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<i128, SaleError> {
+        if e.ledger().timestamp() > deadline {
+            return Err(SaleError::DeadlinePassed);
+        }
+        execute_contribution(
+            &e,
+            participant,
+            payment_token,
+            amount,
+            min_tokens_out,
+            whitelist_proof,
+            voucher,
+        )
+    }
+
+    //Run the checks of contribute without taking anything. The proof and voucher are
+    //checked the same way, but the voucher nonce is not consumed
+
+    fn preview_contribute(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+        amount: i128,
+        whitelist_proof: Option<WhitelistProof>,
+        voucher: Option<SignedVoucher>,
+    ) -> Result<ContributionPreview, SaleError> {
+        let quote = check_contribution_open(&e, payment_token.clone()).and_then(|token_config| {
+            let (max_allocation, _) =
+                check_eligibility(&e, &participant, whitelist_proof, voucher)?;
+            quote_contribution(
+                &e,
+                &participant,
+                payment_token,
+                &token_config,
+                amount,
+                max_allocation,
+            )
+        });
+        let (amount_accepted, tokens_purchased, error_code) = match quote {
+            Ok((amount, amount_purchased)) => (amount, amount_purchased, 0),
+            Err(error) => (0, 0, error as u32),
+        };
+
         let token_params = read_sales_parameters(&e);
-        let payment_rate = read_sale_rate(&e, payment_token.clone());
-        let amount_purchased = amount * payment_rate as i128;
+        let participant_purchase = read_participant_purchase_amount(&e, participant);
+        Ok(ContributionPreview {
+            amount_accepted,
+            tokens_purchased,
+            remaining_personal_cap: (token_params.max_buy as i128 - participant_purchase).max(0),
+            remaining_hard_cap: (token_params.hard_cap as i128 - read_total_sold(&e)).max(0),
+            error_code,
+        })
+    }
+
+    //Withdraw the whole contribution in a payment token during the opt-out window. The
+    //tokens purchased with it go back to the sale and the penalty to the treasury
 
-        if !is_supported {
-            panic!("the token entered is not a supported payment option")
+    fn opt_out(e: Env, participant: Address, payment_token: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Refund)?;
+        participant.require_auth();
+        let config = read_opt_out_config(&e).ok_or(SaleError::OptOutClosed)?;
+        if read_sale_status(&e) != SaleStatus::Active {
+            return Err(SaleError::SaleNotActive);
         }
-        if token_params.end_time < e.ledger().timestamp() {
-            panic!("this sale is over")
+        if e.ledger().timestamp() > config.deadline {
+            return Err(SaleError::OptOutClosed);
         }
-        if token_params.min_buy as u128 > amount_purchased as u128 {
-            panic!("the amount entered is less than min buy")
+        let contribution =
+            read_participant_contribution_amount(&e, participant.clone(), payment_token.clone());
+        if contribution == 0 {
+            return Err(SaleError::NothingToClaim);
         }
+        let tokens_returned =
+            read_participant_token_purchase(&e, participant.clone(), payment_token.clone());
 
-        if (token_params.max_buy as u128) < amount_purchased as u128 {
-            panic!("the amount entered is greater than max buy")
+        clear_participant_contribution_amount(&e, participant.clone(), payment_token.clone());
+        write_total_contribution(&e, payment_token.clone(), -contribution);
+        write_participant_token_purchase(&e, participant.clone(), payment_token.clone(), 0);
+        reduce_participant_purchase_amount(&e, participant.clone(), tokens_returned);
+        write_total_sold(&e, -tokens_returned);
+
+        let penalty = contribution * config.penalty_bps as i128 / MAX_BPS as i128;
+        let amount = contribution - penalty;
+        if amount > 0 {
+            send_token(&e, &payment_token, &participant, amount);
+        }
+        if penalty > 0 {
+            send_token(&e, &payment_token, &config.treasury, penalty);
         }
+        events::publish(
+            &e,
+            symbol_short!("opt_out"),
+            OptOutEvent {
+                participant,
+                payment_token,
+                amount,
+                penalty,
+                tokens_returned,
+                total_sold: read_total_sold(&e),
+            },
+        );
+        Ok(())
+    }
 
-        take_token(&e, &payment_token, &participant, amount as i128);
+    //Settle the outcome once the sale is over and return the unsold tokens to the project
 
-        update_make_contribution_amount(&e, participant.clone(), payment_token, amount as i128);
-        update_participant_purchase_amount(&e, participant, amount_purchased as i128);
-        write_total_sold(&e, amount_purchased as i128);
+    fn finalize_sale(e: Env) -> Result<(), SaleError> {
+        match read_sale_status(&e) {
+            SaleStatus::Ended => {}
+            SaleStatus::NotConfigured | SaleStatus::Upcoming | SaleStatus::Active => {
+                return Err(SaleError::SaleNotOver)
+            }
+            SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+            _ => return Err(SaleError::InvalidStatusTransition),
+        }
+
+        let outcome = read_sale_outcome(&e);
+        write_sale_status(&e, outcome)?;
+
+        let token_address = read_token(&e)?;
+        let hard_cap = read_sales_parameters(&e).hard_cap as i128;
+        let tokens_deposited = read_tokens_deposited(&e);
+        let (tokens_remaining, tokens_returned) = if outcome == SaleStatus::Succeeded {
+            let tokens_remaining = (hard_cap - read_total_sold(&e)).max(0);
+            let sale_token_fee = read_sale_token_fee_amount(&e);
+            if sale_token_fee > 0 {
+                collect_platform_fee(&e, &token_address, sale_token_fee);
+            }
+            let liquidity_tokens = provide_liquidity(&e, &token_address)?;
+            //the unsold tokens go back with what the fee and the liquidity did not use
+            let tokens_allocated = hard_cap - tokens_remaining;
+            (
+                tokens_remaining,
+                tokens_deposited - tokens_allocated - sale_token_fee - liquidity_tokens,
+            )
+        } else {
+            (hard_cap, tokens_deposited)
+        };
+        write_tokens_remaining(&e, tokens_remaining);
+
+        if tokens_returned > 0 {
+            send_token(
+                &e,
+                &token_address,
+                &read_project_wallet(&e)?,
+                tokens_returned,
+            );
+        }
+        events::publish(
+            &e,
+            symbol_short!("finalize"),
+            SaleFinalizedEvent {
+                status: outcome,
+                total_sold: read_total_sold(&e),
+                tokens_remaining,
+            },
+        );
+        Ok(())
+    }
+
+    //Lock tokens of the owner in the contract until unlock_time. Tokens added to an existing
+    //lock keep its unlock time. The LP token is first locked by finalize, so its lock cannot
+    //be opened earlier with a shorter unlock time
+    fn lock_tokens(
+        e: Env,
+        from: Address,
+        token: Address,
+        amount: i128,
+        unlock_time: u64,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &from, Role::Owner)?;
+        if unlock_time <= e.ledger().timestamp() {
+            return Err(SaleError::InvalidConfig);
+        }
+        if let Some(config) = read_liquidity_config(&e) {
+            if config.lp_token == token && read_liquidity_provided(&e, config.payment_token) == 0 {
+                return Err(SaleError::InvalidConfig);
+            }
+        }
+        let lock = add_to_lock(&e, token.clone(), amount, unlock_time)?;
+        take_token(&e, &token, &from, amount);
+        events::publish(
+            &e,
+            symbol_short!("lock"),
+            LockEvent {
+                token,
+                amount: lock.amount,
+                unlock_time: lock.unlock_time,
+            },
+        );
+        Ok(())
+    }
+
+    fn extend_lock(
+        e: Env,
+        caller: Address,
+        token: Address,
+        unlock_time: u64,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Owner)?;
+        let mut lock = read_token_lock(&e, token.clone()).ok_or(SaleError::NoLock)?;
+        if unlock_time < lock.unlock_time {
+            return Err(SaleError::LockShortened);
+        }
+        lock.unlock_time = unlock_time;
+        write_token_lock(&e, token.clone(), Some(lock.clone()));
+        events::publish(
+            &e,
+            symbol_short!("lock_ext"),
+            LockEvent {
+                token,
+                amount: lock.amount,
+                unlock_time,
+            },
+        );
+        Ok(())
+    }
+
+    //Release the whole lock of a token once its unlock time is reached
+    fn withdraw_locked(
+        e: Env,
+        caller: Address,
+        token: Address,
+        recipient: Address,
+    ) -> Result<(), SaleError> {
+        require_role(&e, &caller, Role::Owner)?;
+        let lock = read_token_lock(&e, token.clone()).ok_or(SaleError::NoLock)?;
+        if e.ledger().timestamp() < lock.unlock_time {
+            return Err(SaleError::TokensLocked);
+        }
+        write_token_lock(&e, token.clone(), None);
+        send_token(&e, &token, &recipient, lock.amount);
+        events::publish(
+            &e,
+            symbol_short!("unlock"),
+            UnlockEvent {
+                token,
+                recipient,
+                amount: lock.amount,
+            },
+        );
+        Ok(())
     }
 
-    //Allow participants to claim tokens from successful sale after tge time
+    //Allow participants to claim their vested tokens from successful sale after tge time,
+    //repeatedly as more of their purchase vests
 
-    fn claim_purchased_tokens(e: Env, participant: Address) {
+    fn claim_purchased_tokens(e: Env, participant: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Claim)?;
         participant.require_auth();
-        let total_raised = read_total_sold(&e);
         let token_params = read_sales_parameters(&e);
-        if total_raised < token_params.soft_cap as i128 {
-            panic!("sales not successful, you can withdraw your contribution")
+        match read_sale_status(&e) {
+            SaleStatus::Succeeded | SaleStatus::Finalized => {}
+            SaleStatus::Failed => return Err(SaleError::SaleFailed),
+            SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+            SaleStatus::Ended => return Err(SaleError::SaleNotFinalized),
+            _ => return Err(SaleError::SaleNotOver),
         }
         if token_params.tge_time > e.ledger().timestamp() {
-            panic!("you cannot claim before the TGE time")
+            return Err(SaleError::BeforeTge);
         }
-        let amount_claimable = read_participant_purchase_amount(&e, participant.clone());
-        if amount_claimable == 0 {
-            panic!("this address has nothing to claim")
+        if transfer_claimable_tokens(&e, &participant)? == 0 {
+            return Err(SaleError::NothingToClaim);
         }
-        let token_address = read_token(&e);
-        update_participant_purchase_amount(&e, participant.clone(), 0);
-        send_token(&e, &token_address, &participant, amount_claimable);
+        Ok(())
     }
 
-    //Refund contribution if sale not successful
+    //Claim the vested tokens and, for an oversubscribed sale, the refund of the unfilled
+    //part of every payment token contribution in a single call
 
-    fn claim_refund(e: Env, participant: Address) {
+    fn claim(e: Env, participant: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Claim)?;
         participant.require_auth();
+        match read_sale_status(&e) {
+            SaleStatus::Succeeded | SaleStatus::Finalized => {}
+            SaleStatus::Failed => return Err(SaleError::SaleFailed),
+            SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+            SaleStatus::Ended => return Err(SaleError::SaleNotFinalized),
+            _ => return Err(SaleError::SaleNotOver),
+        }
 
-        let total_raised = read_total_sold(&e);
-        let token_params = read_sales_parameters(&e);
-        if total_raised > token_params.soft_cap as i128 {
-            panic!("sale was successful, claim tokens purchased instead")
+        let refunded = refund_excess_contribution(&e, &participant)?;
+        let claimed = if read_sales_parameters(&e).tge_time <= e.ledger().timestamp() {
+            transfer_claimable_tokens(&e, &participant)?
+        } else {
+            0
+        };
+        if refunded == 0 && claimed == 0 {
+            return Err(SaleError::NothingToClaim);
         }
-        if token_params.end_time > e.ledger().timestamp() {
-            panic!("you cannot claim refund before sale is over")
+        Ok(())
+    }
+
+    //Refund contribution if sale not successful or cancelled
+
+    fn claim_refund(e: Env, participant: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Refund)?;
+        participant.require_auth();
+
+        match read_sale_status(&e) {
+            SaleStatus::Failed | SaleStatus::Cancelled => {}
+            SaleStatus::Succeeded | SaleStatus::Finalized => return Err(SaleError::SaleSucceeded),
+            SaleStatus::Ended => return Err(SaleError::SaleNotFinalized),
+            _ => return Err(SaleError::SaleNotOver),
         }
-        let active_payment_tokens = read_active_payment_tokens(&e);
+        //every registered token is refunded, even if its rate was set to zero since
+        let payment_tokens = read_payment_tokens(&e)?;
 
-        for payment_token in active_payment_tokens.iter() {
+        for payment_token in payment_tokens.iter() {
             let payment_amount_claimable = read_participant_contribution_amount(
                 &e,
                 participant.clone(),
                 payment_token.clone(),
             );
             if payment_amount_claimable > 0 {
-                update_make_contribution_amount(&e, participant.clone(), payment_token.clone(), 0);
+                clear_participant_contribution_amount(
+                    &e,
+                    participant.clone(),
+                    payment_token.clone(),
+                );
                 send_token(&e, &payment_token, &participant, payment_amount_claimable);
+                events::publish(
+                    &e,
+                    symbol_short!("refund"),
+                    RefundEvent {
+                        participant: participant.clone(),
+                        payment_token: payment_token.clone(),
+                        amount: payment_amount_claimable,
+                    },
+                );
             }
         }
+        Ok(())
     }
 
-    fn withdraw_raised_funds(e: Env) {
-        let fund_recipient = read_fund_recipient(&e);
-        let sale_parameter = read_sales_parameters(&e);
-        let total_sold = read_total_sold(&e);
+    //Each fund recipient pulls its share of every payment token, the first withdrawal
+    //finalizes the sale and freezes the recipients
 
-        fund_recipient.require_auth();
-        if sale_parameter.end_time > e.ledger().timestamp() {
-            panic!("the sale is not over, fund can be withdrawn only when sale is over!")
+    fn withdraw_raised_funds(e: Env, recipient: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Withdraw)?;
+        recipient.require_auth();
+        match read_sale_status(&e) {
+            SaleStatus::Succeeded => write_sale_status(&e, SaleStatus::Finalized)?,
+            SaleStatus::Finalized => {}
+            SaleStatus::Ended => return Err(SaleError::SaleNotFinalized),
+            SaleStatus::Failed => return Err(SaleError::SaleFailed),
+            SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+            _ => return Err(SaleError::SaleNotOver),
         }
 
-        if sale_parameter.soft_cap > total_sold as u64 {
-            panic!("the softcap not reached, the sale was not successful!")
-        }
+        //disabled tokens are withdrawn too, they may have been disabled during the sale
+        let payment_tokens = read_payment_tokens(&e)?;
 
-        let active_payment_tokens = read_active_payment_tokens(&e);
+        let mut withdrawn_any = false;
+        for payment_token in payment_tokens.iter() {
+            //the first withdrawal in a token sends the platform fee along
+            let fee = read_payment_fee_amount(&e, payment_token.clone());
+            if fee > 0 && read_fee_collected(&e, payment_token.clone()) == 0 {
+                collect_platform_fee(&e, &payment_token, fee);
+            }
 
-        for payment_token in active_payment_tokens.iter() {
-            let withdrawable_funds = read_total_contribution(&e, payment_token.clone());
+            let withdrawable_funds =
+                read_withdrawable_funds(&e, &recipient, payment_token.clone())?;
             if withdrawable_funds > 0 {
-                write_total_contribution(&e, payment_token.clone(), 0);
-                send_token(&e, &payment_token, &fund_recipient, withdrawable_funds);
+                update_withdrawn_funds(
+                    &e,
+                    recipient.clone(),
+                    payment_token.clone(),
+                    withdrawable_funds,
+                );
+                send_token(&e, &payment_token, &recipient, withdrawable_funds);
+                events::publish(
+                    &e,
+                    symbol_short!("withdraw"),
+                    WithdrawalEvent {
+                        recipient: recipient.clone(),
+                        payment_token: payment_token.clone(),
+                        amount: withdrawable_funds,
+                    },
+                );
+                withdrawn_any = true;
+            }
+        }
+        if !withdrawn_any {
+            if read_released_bps(&e) < MAX_BPS {
+                return Err(SaleError::FundsNotReleased);
+            }
+            return Err(SaleError::FundsAlreadyWithdrawn);
+        }
+        Ok(())
+    }
+
+    //Participants vote with the tokens they purchased to halt the releases of the raised
+    //funds. Once the votes reach the quorum, the funds released so far stay with the
+    //recipients and the rest can be reclaimed
+
+    fn vote_halt(e: Env, participant: Address) -> Result<(), SaleError> {
+        participant.require_auth();
+        let schedule = check_halt_vote_open(&e)?;
+        if read_halt_vote(&e, participant.clone()) > 0 {
+            return Err(SaleError::AlreadyVoted);
+        }
+        let weight = read_participant_purchase_amount(&e, participant.clone());
+        if weight == 0 {
+            return Err(SaleError::NoVotingWeight);
+        }
+
+        write_halt_vote(&e, participant.clone(), weight);
+        let total_votes = read_halt_votes(&e) + weight;
+        write_halt_votes(&e, total_votes);
+        events::publish(
+            &e,
+            symbol_short!("halt_vote"),
+            HaltVoteEvent {
+                participant,
+                weight,
+                total_votes,
+            },
+        );
+
+        if total_votes * MAX_BPS as i128 >= read_total_sold(&e) * schedule.halt_quorum_bps as i128 {
+            let released_bps = read_released_bps(&e);
+            write_halted_release(&e, released_bps);
+            events::publish(
+                &e,
+                symbol_short!("halted"),
+                ReleaseHaltedEvent {
+                    released_bps,
+                    total_votes,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    //After a halt, each participant gives back the unreleased share of its allocation and
+    //reclaims the unreleased funds of every payment token pro-rata to its contribution in
+    //that token. Tokens already claimed beyond the released share cannot be given back, so
+    //the refund shrinks in proportion. The tokens given back go to the project wallet
+
+    fn claim_halt_refund(e: Env, participant: Address) -> Result<(), SaleError> {
+        check_not_paused(&e, PausableOperation::Refund)?;
+        participant.require_auth();
+        let released_bps = read_halted_release(&e).ok_or(SaleError::ReleaseNotHalted)?;
+        if read_halt_refunded(&e, participant.clone()).is_some() {
+            return Err(SaleError::AlreadyRefunded);
+        }
+        let allocation = read_participant_allocation(&e, participant.clone());
+        let released = allocation * released_bps as i128 / MAX_BPS as i128;
+        let kept = released.max(read_participant_claimed_amount(&e, participant.clone()));
+        write_halt_refunded(&e, participant.clone(), kept);
+        if kept == allocation {
+            return Ok(());
+        }
+
+        for payment_token in read_payment_tokens(&e)?.iter() {
+            let total_contribution = read_total_contribution(&e, payment_token.clone());
+            if total_contribution == 0 {
+                continue;
+            }
+            let distributable = read_distributable_funds(&e, payment_token.clone());
+            let unreleased = distributable - distributable * released_bps as i128 / MAX_BPS as i128;
+            let contribution = read_participant_contribution_amount(
+                &e,
+                participant.clone(),
+                payment_token.clone(),
+            );
+            let amount = unreleased * contribution / total_contribution * (allocation - kept)
+                / (allocation - released);
+            if amount > 0 {
+                send_token(&e, &payment_token, &participant, amount);
+                events::publish(
+                    &e,
+                    symbol_short!("halt_rfnd"),
+                    RefundEvent {
+                        participant: participant.clone(),
+                        payment_token: payment_token.clone(),
+                        amount,
+                    },
+                );
             }
         }
+
+        let forfeited = allocation - kept;
+        let project_wallet = read_project_wallet(&e)?;
+        send_token(&e, &read_token(&e)?, &project_wallet, forfeited);
+        events::publish(
+            &e,
+            symbol_short!("forfeit"),
+            TokensForfeitedEvent {
+                participant,
+                project_wallet,
+                amount: forfeited,
+            },
+        );
+        Ok(())
     }
 
-    fn get_sale_token(e: Env) -> Address {
+    fn get_sale_token(e: Env) -> Result<Address, SaleError> {
         read_token(&e)
     }
 
-    fn get_sale_rate(e: Env, payment_token: Address) -> u64 {
-        read_sale_rate(&e, payment_token)
+    fn get_sale_rate(e: Env, payment_token: Address) -> Result<Option<SwapRate>, SaleError> {
+        Ok(read_sale_rate(&e, payment_token))
     }
 
-    fn get_payment_purchases(e: Env,participant: Address, payment_token: Address) -> i128 {
-        let rate=read_sale_rate(&e, payment_token.clone());
-        let total_amount=read_participant_contribution_amount(&e, participant, payment_token);
-        rate as i128*total_amount
+    fn get_payment_purchases(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError> {
+        let total_amount =
+            read_participant_contribution_amount(&e, participant, payment_token.clone());
+        if total_amount == 0 {
+            return Ok(0);
+        }
+        read_conversion_rate(&e, payment_token.clone())?.purchase(total_amount)
     }
 
-    fn get_payment_options(e: Env) -> Vec<Address> {
+    fn get_payment_options(e: Env) -> Result<Vec<Address>, SaleError> {
         read_active_payment_tokens(&e)
     }
 
-    fn get_sales_parameters(e: &Env) -> SalesParameter {
-        read_sales_parameters(e)
+    fn get_sales_parameters(e: &Env) -> Result<SalesParameter, SaleError> {
+        Ok(read_sales_parameters(e))
+    }
+
+    fn get_sale_status(e: Env) -> Result<SaleStatus, SaleError> {
+        Ok(read_sale_status(&e))
+    }
+
+    fn get_participant_total_purchase(e: Env, participant: Address) -> Result<i128, SaleError> {
+        Ok(read_participant_purchase_amount(&e, participant))
+    }
+
+    fn get_participant_allocation(e: Env, participant: Address) -> Result<i128, SaleError> {
+        Ok(read_participant_allocation(&e, participant))
+    }
+
+    fn get_claimable_amount(e: Env, participant: Address) -> Result<i128, SaleError> {
+        match read_sale_status(&e) {
+            SaleStatus::Succeeded | SaleStatus::Finalized => {
+                Ok(read_claimable_amount(&e, participant))
+            }
+            _ => Ok(0),
+        }
     }
 
-    fn get_participant_total_purchase(e: Env, participant: Address) -> i128 {
-        read_participant_purchase_amount(&e, participant)
+    fn get_claimed_amount(e: Env, participant: Address) -> Result<i128, SaleError> {
+        Ok(read_participant_claimed_amount(&e, participant))
     }
 
-    fn get_participant_contribution(e: Env, participant: Address, payment_token: Address) -> i128 {
-        read_participant_contribution_amount(&e, participant, payment_token)
+    fn get_participant_contribution(
+        e: Env,
+        participant: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError> {
+        Ok(read_participant_contribution_amount(
+            &e,
+            participant,
+            payment_token,
+        ))
     }
 
-    fn get_total_sold(e: Env) -> i128 {
-        read_total_sold(&e)
+    fn get_total_sold(e: Env) -> Result<i128, SaleError> {
+        Ok(read_total_sold(&e))
     }
 
-    fn get_total_contribution(e: Env, payment_token: Address) -> i128 {
-        read_total_contribution(&e, payment_token)
+    fn get_total_contribution(e: Env, payment_token: Address) -> Result<i128, SaleError> {
+        Ok(read_total_contribution(&e, payment_token))
     }
 
-    fn get_supported_tokens(e: Env) -> Vec<Address> {
+    fn get_supported_tokens(e: Env) -> Result<Vec<Address>, SaleError> {
         read_payment_tokens(&e)
     }
 
-    fn get_fund_recipient(e: Env) -> Address {
-        read_fund_recipient(&e)
+    //The first recipient, the only one unless the funds are split
+
+    fn get_fund_recipient(e: Env) -> Result<Address, SaleError> {
+        let recipients = read_fund_recipients(&e)?;
+        recipients
+            .first()
+            .map(|entry| entry.recipient)
+            .ok_or(SaleError::FundsRecipientNotSet)
+    }
+
+    fn get_fund_recipients(e: Env) -> Result<Vec<FundRecipient>, SaleError> {
+        read_fund_recipients(&e)
+    }
+
+    fn get_platform_fee(e: Env) -> Result<Option<PlatformFee>, SaleError> {
+        Ok(read_platform_fee(&e))
+    }
+
+    fn get_fee_collected(e: Env, token: Address) -> Result<i128, SaleError> {
+        Ok(read_fee_collected(&e, token))
     }
 
-    fn get_admin(e: &Env) -> Address {
+    fn get_liquidity_config(e: Env) -> Result<Option<LiquidityConfig>, SaleError> {
+        Ok(read_liquidity_config(&e))
+    }
+
+    fn get_lock(e: Env, token: Address) -> Result<Option<TokenLock>, SaleError> {
+        Ok(read_token_lock(&e, token))
+    }
+
+    fn get_release_schedule(e: Env) -> Result<Option<ReleaseSchedule>, SaleError> {
+        Ok(read_release_schedule(&e))
+    }
+
+    fn get_opt_out_config(e: Env) -> Result<Option<OptOutConfig>, SaleError> {
+        Ok(read_opt_out_config(&e))
+    }
+
+    fn get_released_bps(e: Env) -> Result<u32, SaleError> {
+        Ok(read_released_bps(&e))
+    }
+
+    fn get_halt_votes(e: Env) -> Result<i128, SaleError> {
+        Ok(read_halt_votes(&e))
+    }
+
+    fn get_halt_vote(e: Env, participant: Address) -> Result<i128, SaleError> {
+        Ok(read_halt_vote(&e, participant))
+    }
+
+    fn get_liquidity_provided(e: Env, payment_token: Address) -> Result<i128, SaleError> {
+        Ok(read_liquidity_provided(&e, payment_token))
+    }
+
+    fn get_withdrawable_funds(
+        e: Env,
+        recipient: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError> {
+        match read_sale_status(&e) {
+            SaleStatus::Succeeded | SaleStatus::Finalized => {
+                read_withdrawable_funds(&e, &recipient, payment_token)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn get_withdrawn_funds(
+        e: Env,
+        recipient: Address,
+        payment_token: Address,
+    ) -> Result<i128, SaleError> {
+        Ok(read_withdrawn_funds(&e, recipient, payment_token))
+    }
+
+    fn get_payment_token_config(
+        e: Env,
+        payment_token: Address,
+    ) -> Result<Option<PaymentTokenConfig>, SaleError> {
+        Ok(read_payment_token_config(&e, payment_token))
+    }
+
+    fn get_project_wallet(e: Env) -> Result<Address, SaleError> {
+        read_project_wallet(&e)
+    }
+
+    fn get_tokens_remaining(e: Env) -> Result<i128, SaleError> {
+        Ok(read_tokens_remaining(&e))
+    }
+
+    fn get_partial_fill(e: Env) -> Result<bool, SaleError> {
+        Ok(read_partial_fill(&e))
+    }
+
+    fn get_oversubscription(e: Env) -> Result<bool, SaleError> {
+        Ok(read_oversubscription(&e))
+    }
+
+    fn get_price_oracle(e: Env) -> Result<Option<OracleConfig>, SaleError> {
+        Ok(read_oracle_config(&e))
+    }
+
+    fn get_merkle_root(e: Env) -> Result<Option<BytesN<32>>, SaleError> {
+        Ok(read_merkle_root(&e))
+    }
+
+    fn get_kyc_signer(e: Env) -> Result<Option<BytesN<32>>, SaleError> {
+        Ok(read_kyc_signer(&e))
+    }
+
+    fn get_admin(e: &Env) -> Result<Address, SaleError> {
         read_administrator(e)
     }
 
-    fn get_current_timestamp(e: Env) -> u64 {
-        e.ledger().timestamp()
+    fn get_pending_admin(e: Env) -> Result<Option<Address>, SaleError> {
+        Ok(read_pending_administrator(&e))
+    }
+
+    fn has_role(e: Env, role: Role, account: Address) -> Result<bool, SaleError> {
+        Ok(has_role(&e, role, &account))
+    }
+
+    fn get_current_timestamp(e: Env) -> Result<u64, SaleError> {
+        Ok(e.ledger().timestamp())
+    }
+
+    fn is_paused(e: Env, operation: PausableOperation) -> Result<bool, SaleError> {
+        Ok(read_is_paused(&e, operation))
+    }
+}
+
+fn update_payment_token_config(
+    e: &Env,
+    payment_token: Address,
+    update: impl FnOnce(&mut PaymentTokenConfig),
+) -> Result<(), SaleError> {
+    let mut config = read_payment_token_config(e, payment_token.clone())
+        .ok_or(SaleError::UnsupportedPaymentToken)?;
+    update(&mut config);
+    write_payment_token_config(e, payment_token.clone(), &config);
+    events::publish(
+        e,
+        symbol_short!("pay_cfg"),
+        PaymentTokenConfigEvent {
+            payment_token,
+            config,
+        },
+    );
+    Ok(())
+}
+
+//Take the contribution and record the purchase, returns the tokens purchased
+fn execute_contribution(
+    e: &Env,
+    participant: Address,
+    payment_token: Address,
+    amount: i128,
+    min_tokens_out: i128,
+    whitelist_proof: Option<WhitelistProof>,
+    voucher: Option<SignedVoucher>,
+) -> Result<i128, SaleError> {
+    participant.require_auth();
+    let token_config = check_contribution_open(e, payment_token.clone())?;
+    let (max_allocation, voucher_nonce) =
+        check_eligibility(e, &participant, whitelist_proof, voucher)?;
+    let (amount, amount_purchased) = quote_contribution(
+        e,
+        &participant,
+        payment_token.clone(),
+        &token_config,
+        amount,
+        max_allocation,
+    )?;
+    if amount_purchased < min_tokens_out {
+        return Err(SaleError::SlippageExceeded);
+    }
+
+    if let Some(nonce) = voucher_nonce {
+        write_nonce_consumed(e, participant.clone(), nonce);
+    }
+    take_token(e, &payment_token, &participant, amount);
+
+    update_make_contribution_amount(e, participant.clone(), payment_token.clone(), amount);
+    update_participant_purchase_amount(e, participant.clone(), amount_purchased)?;
+    write_participant_token_purchase(
+        e,
+        participant.clone(),
+        payment_token.clone(),
+        read_participant_token_purchase(e, participant.clone(), payment_token.clone())
+            + amount_purchased,
+    );
+    write_total_sold(e, amount_purchased);
+
+    events::publish(
+        e,
+        symbol_short!("contrib"),
+        ContributionEvent {
+            participant: participant.clone(),
+            payment_token: payment_token.clone(),
+            amount,
+            tokens_purchased: amount_purchased,
+            participant_contribution: read_participant_contribution_amount(
+                e,
+                participant.clone(),
+                payment_token.clone(),
+            ),
+            participant_purchase: read_participant_purchase_amount(e, participant),
+            total_contribution: read_total_contribution(e, payment_token),
+            total_sold: read_total_sold(e),
+        },
+    );
+
+    //close the sale early once the hard cap is reached
+    let hard_cap = read_sales_parameters(e).hard_cap as i128;
+    if !read_oversubscription(e) && read_total_sold(e) >= hard_cap {
+        write_sale_status(e, SaleStatus::Ended)?;
+    }
+    Ok(amount_purchased)
+}
+
+//The checks that do not depend on the amount, returns the payment token config
+fn check_contribution_open(
+    e: &Env,
+    payment_token: Address,
+) -> Result<PaymentTokenConfig, SaleError> {
+    check_not_paused(e, PausableOperation::Contribute)?;
+    let token_config =
+        read_payment_token_config(e, payment_token).ok_or(SaleError::UnsupportedPaymentToken)?;
+    if !token_config.enabled {
+        return Err(SaleError::PaymentTokenDisabled);
+    }
+    match read_sale_status(e) {
+        SaleStatus::Active => {}
+        SaleStatus::Upcoming => return Err(SaleError::SaleNotStarted),
+        _ => return Err(SaleError::SaleNotActive),
+    }
+    Ok(token_config)
+}
+
+//Apply the rate, the caps and the partial fill to a contribution, returns the amount
+//accepted and the tokens it purchases. A max_allocation of 0 is uncapped
+fn quote_contribution(
+    e: &Env,
+    participant: &Address,
+    payment_token: Address,
+    token_config: &PaymentTokenConfig,
+    amount: i128,
+    max_allocation: i128,
+) -> Result<(i128, i128), SaleError> {
+    let token_params = read_sales_parameters(e);
+    let rate = read_conversion_rate(e, payment_token.clone())?;
+    let amount_purchased = rate.purchase(amount)?;
+    if token_params.min_buy as u128 > amount_purchased as u128 {
+        return Err(SaleError::BelowMinBuy);
+    }
+
+    if (token_params.max_buy as u128) < amount_purchased as u128 {
+        return Err(SaleError::MaxBuyExceeded);
+    }
+
+    //only the part of the contribution that fits under the hard cap is accepted,
+    //unless oversubscribed purchases are scaled down at finalization instead
+    let oversubscribed = read_oversubscription(e);
+    let remaining_tokens = token_params.hard_cap as i128 - read_total_sold(e);
+    let (amount, amount_purchased) = if !oversubscribed && amount_purchased > remaining_tokens {
+        if !read_partial_fill(e) {
+            return Err(SaleError::HardCapExceeded);
+        }
+        let accepted_amount = rate.payment(remaining_tokens.max(0))?;
+        (accepted_amount, rate.purchase(accepted_amount)?)
+    } else {
+        (amount, amount_purchased)
+    };
+    if amount_purchased <= 0 {
+        return Err(SaleError::HardCapExceeded);
+    }
+    if amount < token_config.min_contribution {
+        return Err(SaleError::BelowMinBuy);
+    }
+    if token_config.max_contribution > 0
+        && read_participant_contribution_amount(e, participant.clone(), payment_token.clone())
+            + amount
+            > token_config.max_contribution
+    {
+        return Err(SaleError::MaxBuyExceeded);
+    }
+    if token_config.raise_cap > 0
+        && read_total_contribution(e, payment_token.clone()) + amount > token_config.raise_cap
+    {
+        return Err(SaleError::HardCapExceeded);
+    }
+    if max_allocation > 0
+        && read_participant_purchase_amount(e, participant.clone()) + amount_purchased
+            > max_allocation
+    {
+        return Err(SaleError::AllocationExceeded);
+    }
+    if read_participant_purchase_amount(e, participant.clone()) + amount_purchased
+        > token_params.max_buy as i128
+    {
+        return Err(SaleError::MaxBuyExceeded);
+    }
+    Ok((amount, amount_purchased))
+}
+
+fn single_fund_recipient(e: &Env, recipient: Address) -> Vec<FundRecipient> {
+    vec![
+        e,
+        FundRecipient {
+            recipient,
+            share_bps: MAX_BPS,
+        },
+    ]
+}
+
+//The recipients can change until the first withdrawal, their shares must sum to 10000
+fn update_fund_recipients(e: &Env, recipients: Vec<FundRecipient>) -> Result<(), SaleError> {
+    if read_sale_status(e) == SaleStatus::Finalized {
+        return Err(SaleError::FundsAlreadyWithdrawn);
+    }
+
+    let mut total_bps: u32 = 0;
+    for (index, entry) in recipients.iter().enumerate() {
+        let duplicated = recipients
+            .iter()
+            .skip(index + 1)
+            .any(|other| other.recipient == entry.recipient);
+        if entry.share_bps == 0 || duplicated {
+            return Err(SaleError::InvalidConfig);
+        }
+        total_bps = total_bps.saturating_add(entry.share_bps);
+    }
+    if total_bps != MAX_BPS {
+        return Err(SaleError::InvalidConfig);
+    }
+
+    write_fund_recipients(e, &recipients);
+    events::publish(
+        e,
+        symbol_short!("recipient"),
+        FundRecipientsSetEvent { recipients },
+    );
+    Ok(())
+}
+
+fn update_platform_fee(e: &Env, fee: PlatformFee) -> Result<(), SaleError> {
+    if fee.fee_bps > MAX_BPS || fee.sale_token_fee_bps > MAX_BPS {
+        return Err(SaleError::InvalidConfig);
+    }
+    write_platform_fee(e, Some(fee.clone()));
+    events::publish(e, symbol_short!("fee_set"), PlatformFeeSetEvent { fee });
+    Ok(())
+}
+
+//Send the platform fee in a token to the treasury, once per token
+fn collect_platform_fee(e: &Env, token_address: &Address, amount: i128) {
+    let treasury = match read_platform_fee(e) {
+        Some(fee) => fee.treasury,
+        None => return,
+    };
+    write_fee_collected(e, token_address.clone(), amount);
+    send_token(e, token_address, &treasury, amount);
+    events::publish(
+        e,
+        symbol_short!("fee"),
+        FeeCollectedEvent {
+            treasury,
+            token: token_address.clone(),
+            amount,
+        },
+    );
+}
+
+//Sale tokens taken at configuration: the hard cap, the sale token fee deposit and the
+//liquidity reserve
+fn read_tokens_deposited(e: &Env) -> i128 {
+    read_sales_parameters(e).hard_cap as i128
+        + read_sale_token_fee_deposit(e)
+        + read_liquidity_reserve(e)
+}
+
+fn update_liquidity_config(e: &Env, config: LiquidityConfig) -> Result<(), SaleError> {
+    if read_payment_token_config(e, config.payment_token.clone()).is_none() {
+        return Err(SaleError::UnsupportedPaymentToken);
+    }
+    if config.liquidity_bps > MAX_BPS
+        || config.max_slippage_bps > MAX_BPS
+        || config.sale_token_reserve < 0
+        || config.listing_rate.numerator == 0
+        || config.listing_rate.denominator == 0
+        || (config.lp_unlock_time > 0 && config.lp_unlock_time <= e.ledger().timestamp())
+    {
+        return Err(SaleError::InvalidConfig);
+    }
+    write_liquidity_config(e, Some(config.clone()));
+    events::publish(e, symbol_short!("liq_set"), LiquiditySetEvent { config });
+    Ok(())
+}
+
+fn update_opt_out_config(e: &Env, config: Option<OptOutConfig>) -> Result<(), SaleError> {
+    match config {
+        Some(config) => {
+            if config.penalty_bps > MAX_BPS {
+                return Err(SaleError::InvalidConfig);
+            }
+            write_opt_out_config(e, Some(config.clone()));
+            events::publish(e, symbol_short!("opt_set"), OptOutSetEvent { config });
+        }
+        None => {
+            if let Some(config) = read_opt_out_config(e) {
+                write_opt_out_config(e, None);
+                events::publish(e, symbol_short!("opt_rm"), OptOutSetEvent { config });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn update_release_schedule(e: &Env, schedule: ReleaseSchedule) -> Result<(), SaleError> {
+    if schedule.tranches.is_empty()
+        || schedule.halt_quorum_bps == 0
+        || schedule.halt_quorum_bps > MAX_BPS
+    {
+        return Err(SaleError::InvalidConfig);
+    }
+    let mut total_bps = 0;
+    let mut last_release_time = None;
+    for tranche in schedule.tranches.iter() {
+        if tranche.release_bps == 0
+            || tranche.release_bps > MAX_BPS - total_bps
+            || last_release_time.is_some_and(|time| tranche.release_time <= time)
+        {
+            return Err(SaleError::InvalidConfig);
+        }
+        total_bps += tranche.release_bps;
+        last_release_time = Some(tranche.release_time);
+    }
+    if total_bps != MAX_BPS {
+        return Err(SaleError::InvalidConfig);
+    }
+    write_release_schedule(e, Some(schedule.clone()));
+    events::publish(e, symbol_short!("rel_set"), ReleaseSetEvent { schedule });
+    Ok(())
+}
+
+//Halt votes are open once the sale succeeded, until the releases are halted or complete
+fn check_halt_vote_open(e: &Env) -> Result<ReleaseSchedule, SaleError> {
+    match read_sale_status(e) {
+        SaleStatus::Succeeded | SaleStatus::Finalized => {}
+        SaleStatus::Ended => return Err(SaleError::SaleNotFinalized),
+        SaleStatus::Failed => return Err(SaleError::SaleFailed),
+        SaleStatus::Cancelled => return Err(SaleError::SaleCancelled),
+        _ => return Err(SaleError::SaleNotOver),
+    }
+    let schedule = read_release_schedule(e).ok_or(SaleError::HaltVoteClosed)?;
+    if read_halted_release(e).is_some() {
+        return Err(SaleError::HaltVoteClosed);
+    }
+    if read_released_bps(e) == MAX_BPS {
+        return Err(SaleError::HaltVoteClosed);
+    }
+    Ok(schedule)
+}
+
+//Pair liquidity_bps of the raised funds with sale tokens at the listing rate, within the
+//reserve, and add them to the pool. Returns the sale tokens used
+fn provide_liquidity(e: &Env, sale_token: &Address) -> Result<i128, SaleError> {
+    let config = match read_liquidity_config(e) {
+        Some(config) => config,
+        None => return Ok(0),
+    };
+    let payment_token = config.payment_token.clone();
+    let raised = read_raised_amount(e, payment_token.clone())
+        - read_payment_fee_amount(e, payment_token.clone());
+    let listing_rate = conversion_rate(e, payment_token.clone(), config.listing_rate)?;
+
+    let mut payment_amount = raised * config.liquidity_bps as i128 / MAX_BPS as i128;
+    let mut token_amount = listing_rate.purchase(payment_amount)?;
+    if token_amount > config.sale_token_reserve {
+        payment_amount = listing_rate.payment(config.sale_token_reserve)?;
+        token_amount = listing_rate.purchase(payment_amount)?;
+    }
+    if payment_amount <= 0 || token_amount <= 0 {
+        return Ok(0);
+    }
+
+    //the router pulls both tokens with the allowances, which expire with this ledger. Locked
+    //LP tokens are minted to the contract itself
+    let contract_address = e.current_contract_address();
+    let lp_locked = config.lp_unlock_time > 0;
+    let lp_recipient = if lp_locked {
+        contract_address.clone()
+    } else {
+        config.lp_recipient.clone()
+    };
+    let expiration_ledger = e.ledger().sequence();
+    token::Client::new(e, sale_token).approve(
+        &contract_address,
+        &config.router,
+        &token_amount,
+        &expiration_ledger,
+    );
+    token::Client::new(e, &payment_token).approve(
+        &contract_address,
+        &config.router,
+        &payment_amount,
+        &expiration_ledger,
+    );
+    let min_amount =
+        |amount: i128| amount * (MAX_BPS - config.max_slippage_bps) as i128 / MAX_BPS as i128;
+    let (token_used, payment_used, lp_amount) = LiquidityRouterClient::new(e, &config.router)
+        .add_liquidity(
+            &contract_address,
+            sale_token,
+            &payment_token,
+            &token_amount,
+            &payment_amount,
+            &min_amount(token_amount),
+            &min_amount(payment_amount),
+            &lp_recipient,
+            &e.ledger().timestamp(),
+        );
+
+    write_liquidity_provided(e, payment_token.clone(), payment_used);
+    events::publish(
+        e,
+        symbol_short!("liquidity"),
+        LiquidityAddedEvent {
+            router: config.router,
+            payment_token,
+            payment_amount: payment_used,
+            token_amount: token_used,
+            lp_amount,
+        },
+    );
+
+    if lp_locked && lp_amount > 0 {
+        let lock = add_to_lock(e, config.lp_token.clone(), lp_amount, config.lp_unlock_time)?;
+        events::publish(
+            e,
+            symbol_short!("lock"),
+            LockEvent {
+                token: config.lp_token,
+                amount: lock.amount,
+                unlock_time: lock.unlock_time,
+            },
+        );
+    }
+    Ok(token_used)
+}
+
+//Store the sales parameters and take the sale tokens deposited from the depositor
+fn configure_sale(
+    e: &Env,
+    depositor: &Address,
+    parameters: SalesParameter,
+) -> Result<(), SaleError> {
+    write_sales_parameters(e, parameters)?;
+    let tokens_deposited = read_tokens_deposited(e);
+
+    let token_address = read_token(e)?;
+    take_token(e, &token_address, depositor, tokens_deposited);
+    events::publish(
+        e,
+        symbol_short!("params"),
+        SaleParametersSetEvent {
+            parameters: read_sales_parameters(e),
+            tokens_deposited,
+        },
+    );
+    Ok(())
+}
+
+//Send the vested tokens not claimed yet, returns the amount sent
+fn transfer_claimable_tokens(e: &Env, participant: &Address) -> Result<i128, SaleError> {
+    let amount_claimable = read_claimable_amount(e, participant.clone());
+    if amount_claimable == 0 {
+        return Ok(0);
+    }
+    let token_address = read_token(e)?;
+    update_participant_claimed_amount(e, participant.clone(), amount_claimable);
+    send_token(e, &token_address, participant, amount_claimable);
+    events::publish(
+        e,
+        symbol_short!("claim"),
+        ClaimEvent {
+            participant: participant.clone(),
+            amount: amount_claimable,
+            total_claimed: read_participant_claimed_amount(e, participant.clone()),
+        },
+    );
+    Ok(amount_claimable)
+}
+
+//Refund once the unfilled part of an oversubscribed purchase, returns the amount sent
+//over all payment tokens
+fn refund_excess_contribution(e: &Env, participant: &Address) -> Result<i128, SaleError> {
+    if read_excess_refunded(e, participant.clone()) {
+        return Ok(0);
+    }
+    write_excess_refunded(e, participant.clone());
+
+    let mut total_refunded = 0;
+    for payment_token in read_payment_tokens(e)?.iter() {
+        let excess =
+            read_participant_excess_contribution(e, participant.clone(), payment_token.clone());
+        if excess > 0 {
+            send_token(e, &payment_token, participant, excess);
+            events::publish(
+                e,
+                symbol_short!("refund"),
+                RefundEvent {
+                    participant: participant.clone(),
+                    payment_token: payment_token.clone(),
+                    amount: excess,
+                },
+            );
+            total_refunded += excess;
+        }
     }
+    Ok(total_refunded)
 }
@@ -0,0 +1,60 @@
+use soroban_sdk::contracterror;
+
+//A contract spec holds at most 50 error cases, so related failures share a variant
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum SaleError {
+    AlreadyInitialized = 1,      //the contract already has an admin
+    AdminNotSet = 2,             //the contract has not been initialized
+    SaleTokenNotSet = 3,         //no token to be sold has been set
+    FundsRecipientNotSet = 4,    //no wallet has been set to receive the raised funds
+    PaymentTokenNotFound = 5,    //a payment token index has no stored address
+    UnsupportedPaymentToken = 6, //the token is not a supported payment option
+    SwapRateNotSet = 7,          //the payment token has no swap rate
+    InvalidStatusTransition = 8, //the sale cannot be finalized or cancelled in its status
+    SaleNotStarted = 9,
+    SaleNotActive = 10,
+    SaleNotOver = 11,
+    SaleNotFinalized = 12,
+    SaleCancelled = 13,
+    SaleSucceeded = 14, //the soft cap was reached, tokens are claimed instead of refunded
+    SaleFailed = 15,    //the soft cap was not reached, contributions are refunded
+    BelowMinBuy = 16,   //below the sale's min buy or the payment token's min contribution
+    MaxBuyExceeded = 17, //the purchase or contribution would go above the participant's cap
+    HardCapExceeded = 18, //the sale's hard cap is reached or crossed, or the token's raise cap
+    BeforeTge = 19,
+    NothingToClaim = 20, //no tokens, contribution or refund is owed to the participant
+    FundsAlreadyWithdrawn = 21,
+    OperationPaused = 22,
+    SaleAlreadyStarted = 23, //the setting can only change before the sale starts
+    NotWhitelisted = 24,     //missing or invalid merkle proof for a gated sale
+    AllocationExceeded = 25, //the purchase goes above the participant's max allocation
+    InvalidVoucher = 26,     //issued for another participant, a bad signature traps instead
+    VoucherExpired = 27,
+    VoucherAlreadyUsed = 28,    //the voucher nonce has already been consumed
+    Unauthorized = 29,          //the caller lacks the role required or is not a fund recipient
+    InvalidRole = 30,           //the owner role cannot be granted, revoked or renounced
+    NoPendingAdmin = 31,        //no admin transfer has been proposed
+    InvalidPaymentOptions = 32, //no, duplicated or zero rate payment options, or the sale token
+    PaymentTokenDisabled = 33,
+    PaymentTokenInUse = 34, //a payment token with contributions cannot be removed
+    ArithmeticOverflow = 35, //the amount is too large to be converted at the swap rate
+    //the sales parameters, fund recipients, oracle, fee, liquidity, release, opt-out or lock
+    //settings are out of range
+    InvalidConfig = 36,
+    PriceUnavailable = 37, //the oracle has no price for the payment token, or only a stale one
+    PriceDeviationTooHigh = 38, //the last oracle price is too far from the recent average
+    DeadlinePassed = 39,   //the contribution was submitted after its deadline
+    SlippageExceeded = 40, //fewer tokens would be purchased than the minimum requested
+    LockShortened = 41,    //a lock can only be extended
+    TokensLocked = 42,     //the unlock time has not been reached
+    NoLock = 43,
+    FundsNotReleased = 44, //the next tranche of the raised funds is not released yet
+    HaltVoteClosed = 45,   //no release schedule, or the releases are already halted or complete
+    ReleaseNotHalted = 46,
+    NoVotingWeight = 47, //the participant purchased no tokens
+    AlreadyVoted = 48,
+    AlreadyRefunded = 49,
+    OptOutClosed = 50, //no opt-out window, or its deadline has passed
+}
@@ -0,0 +1,311 @@
+use soroban_sdk::{contracttype, symbol_short, Address, BytesN, Env, IntoVal, Symbol, Val, Vec};
+
+use crate::storage_types::{
+    FundRecipient, LiquidityConfig, OptOutConfig, OracleConfig, PausableOperation,
+    PaymentTokenConfig, PlatformFee, ReleaseSchedule, Role, SaleStatus, SalesParameter, SwapRate,
+};
+
+// Event schema
+//
+// Every event is published with the topics (SALE_TOPIC, EVENTS_VERSION, name) and one of
+// the data structs below. EVENTS_VERSION is bumped whenever a data struct changes in a way
+// that is not backwards compatible, so indexers can decode each version separately.
+//
+// name        data
+// init        InitializedEvent
+// adm_prop    AdminTransferEvent
+// adm_cncl    AdminTransferEvent
+// admin_set   AdminTransferEvent
+// grant       RoleEvent
+// revoke      RoleEvent
+// sale_tkn    SaleTokenSetEvent
+// pay_tkn     PaymentTokenSetEvent
+// pay_cfg     PaymentTokenConfigEvent
+// pay_rm      PaymentTokenSetEvent
+// rate        SwapRateSetEvent
+// oracle      OracleSetEvent
+// oracle_rm   OracleSetEvent (the config removed)
+// params      SaleParametersSetEvent
+// recipient   FundRecipientsSetEvent
+// fee_set     PlatformFeeSetEvent
+// liq_set     LiquiditySetEvent
+// rel_set     ReleaseSetEvent
+// opt_set     OptOutSetEvent
+// opt_rm      OptOutSetEvent (the config removed)
+// wallet      ProjectWalletSetEvent
+// part_fill   PartialFillSetEvent
+// oversub     OversubscriptionSetEvent
+// whitelist   WhitelistSetEvent
+// wl_rm       WhitelistSetEvent (the root removed)
+// kyc_sign    KycSignerSetEvent
+// kyc_rm      KycSignerSetEvent (the key removed)
+// pause       PauseEvent
+// unpause     PauseEvent
+// cancel      SaleCancelledEvent
+// contrib     ContributionEvent
+// opt_out     OptOutEvent
+// finalize    SaleFinalizedEvent
+// claim       ClaimEvent
+// refund      RefundEvent
+// withdraw    WithdrawalEvent
+// fee         FeeCollectedEvent
+// liquidity   LiquidityAddedEvent
+// lock        LockEvent
+// lock_ext    LockEvent
+// unlock      UnlockEvent
+// halt_vote   HaltVoteEvent
+// halted      ReleaseHaltedEvent
+// halt_rfnd   RefundEvent
+// forfeit     TokensForfeitedEvent
+
+pub(crate) const SALE_TOPIC: Symbol = symbol_short!("sale");
+pub(crate) const EVENTS_VERSION: u32 = 1;
+
+#[derive(Clone)]
+#[contracttype]
+pub struct InitializedEvent {
+    pub admin: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct AdminTransferEvent {
+    pub admin: Address,
+    pub new_admin: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct RoleEvent {
+    pub role: Role,
+    pub account: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleTokenSetEvent {
+    pub token: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PaymentTokenSetEvent {
+    pub payment_token: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PaymentTokenConfigEvent {
+    pub payment_token: Address,
+    pub config: PaymentTokenConfig,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SwapRateSetEvent {
+    pub payment_token: Address,
+    pub rate: SwapRate,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OracleSetEvent {
+    pub config: OracleConfig,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleParametersSetEvent {
+    pub parameters: SalesParameter,
+    pub tokens_deposited: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct FundRecipientsSetEvent {
+    pub recipients: Vec<FundRecipient>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PlatformFeeSetEvent {
+    pub fee: PlatformFee,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct LiquiditySetEvent {
+    pub config: LiquidityConfig,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ReleaseSetEvent {
+    pub schedule: ReleaseSchedule,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OptOutSetEvent {
+    pub config: OptOutConfig,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ProjectWalletSetEvent {
+    pub wallet: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PartialFillSetEvent {
+    pub enabled: bool,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OversubscriptionSetEvent {
+    pub enabled: bool,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct WhitelistSetEvent {
+    pub root: BytesN<32>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct KycSignerSetEvent {
+    pub signer: BytesN<32>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PauseEvent {
+    pub operation: PausableOperation,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleCancelledEvent {
+    pub tokens_returned: i128,
+    pub total_sold: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ContributionEvent {
+    pub participant: Address,
+    pub payment_token: Address,
+    pub amount: i128,
+    pub tokens_purchased: i128,
+    pub participant_contribution: i128, //running total of the participant in this payment token
+    pub participant_purchase: i128,     //running total of tokens bought by the participant
+    pub total_contribution: i128,       //running total raised in this payment token
+    pub total_sold: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleFinalizedEvent {
+    pub status: SaleStatus,
+    pub total_sold: i128,
+    pub tokens_remaining: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ClaimEvent {
+    pub participant: Address,
+    pub amount: i128,
+    pub total_claimed: i128, //running total claimed by the participant
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct RefundEvent {
+    pub participant: Address,
+    pub payment_token: Address,
+    pub amount: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct WithdrawalEvent {
+    pub recipient: Address,
+    pub payment_token: Address,
+    pub amount: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct FeeCollectedEvent {
+    pub treasury: Address,
+    pub token: Address, //a payment token, or the sale token for the sale token fee
+    pub amount: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct LiquidityAddedEvent {
+    pub router: Address,
+    pub payment_token: Address,
+    pub payment_amount: i128,
+    pub token_amount: i128,
+    pub lp_amount: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct LockEvent {
+    pub token: Address,
+    pub amount: i128, //total amount locked
+    pub unlock_time: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct UnlockEvent {
+    pub token: Address,
+    pub recipient: Address,
+    pub amount: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OptOutEvent {
+    pub participant: Address,
+    pub payment_token: Address,
+    pub amount: i128,  //contribution sent back, less the penalty
+    pub penalty: i128, //sent to the treasury
+    pub tokens_returned: i128,
+    pub total_sold: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct HaltVoteEvent {
+    pub participant: Address,
+    pub weight: i128,
+    pub total_votes: i128, //weight of all the votes once this one is counted
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ReleaseHaltedEvent {
+    pub released_bps: u32, //share of the raised funds the recipients keep
+    pub total_votes: i128,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct TokensForfeitedEvent {
+    pub participant: Address,
+    pub project_wallet: Address,
+    pub amount: i128, //sale tokens given back after a halt refund
+}
+
+pub fn publish<D: IntoVal<Env, Val>>(e: &Env, name: Symbol, data: D) {
+    e.events().publish((SALE_TOPIC, EVENTS_VERSION, name), data);
+}
@@ -0,0 +1,69 @@
+use soroban_sdk::{Address, Env};
+
+use crate::balances::{read_raised_amount, read_total_sold};
+use crate::sale_details::read_sales_parameters;
+use crate::storage_types::{DataKey, PlatformFee, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+use crate::vesting::MAX_BPS;
+
+pub fn read_platform_fee(e: &Env) -> Option<PlatformFee> {
+    let key = DataKey::PlatformFee;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_platform_fee(e: &Env, fee: Option<PlatformFee>) {
+    let key = DataKey::PlatformFee;
+    match fee {
+        Some(fee) => e.storage().instance().set(&key, &fee),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//Fee owed to the platform on the funds raised in a payment token, rounded down
+pub fn read_payment_fee_amount(e: &Env, payment_token: Address) -> i128 {
+    match read_platform_fee(e) {
+        Some(fee) => read_raised_amount(e, payment_token) * fee.fee_bps as i128 / MAX_BPS as i128,
+        None => 0,
+    }
+}
+
+pub fn read_fee_collected(e: &Env, token_address: Address) -> i128 {
+    let key = DataKey::FeeCollected(token_address);
+    if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn write_fee_collected(e: &Env, token_address: Address, amount: i128) {
+    let key = DataKey::FeeCollected(token_address);
+    e.storage().persistent().set(&key, &amount);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+//Sale tokens deposited on top of the hard cap to pay the sale token fee on a sold out sale
+pub fn read_sale_token_fee_deposit(e: &Env) -> i128 {
+    match read_platform_fee(e) {
+        Some(fee) => {
+            read_sales_parameters(e).hard_cap as i128 * fee.sale_token_fee_bps as i128
+                / MAX_BPS as i128
+        }
+        None => 0,
+    }
+}
+
+//Sale token fee owed on the tokens actually allocated, never more than the deposit
+pub fn read_sale_token_fee_amount(e: &Env) -> i128 {
+    match read_platform_fee(e) {
+        Some(fee) => {
+            let allocated = read_total_sold(e).min(read_sales_parameters(e).hard_cap as i128);
+            allocated * fee.sale_token_fee_bps as i128 / MAX_BPS as i128
+        }
+        None => 0,
+    }
+}
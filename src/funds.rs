@@ -0,0 +1,88 @@
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::balances::read_raised_amount;
+use crate::errors::SaleError;
+use crate::fees::read_payment_fee_amount;
+use crate::liquidity::read_liquidity_provided;
+use crate::release::read_released_bps;
+use crate::storage_types::{DataKey, FundRecipient, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+use crate::vesting::MAX_BPS;
+
+pub fn read_fund_recipients(e: &Env) -> Result<Vec<FundRecipient>, SaleError> {
+    let key = DataKey::FundRecipients;
+    e.storage()
+        .instance()
+        .get(&key)
+        .ok_or(SaleError::FundsRecipientNotSet)
+}
+
+pub fn write_fund_recipients(e: &Env, recipients: &Vec<FundRecipient>) {
+    let key = DataKey::FundRecipients;
+    e.storage().instance().set(&key, recipients);
+}
+
+pub fn read_withdrawn_funds(e: &Env, recipient: Address, payment_token: Address) -> i128 {
+    let key = DataKey::FundsWithdrawn(recipient, payment_token);
+    if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn update_withdrawn_funds(e: &Env, recipient: Address, payment_token: Address, amount: i128) {
+    let key = DataKey::FundsWithdrawn(recipient.clone(), payment_token.clone());
+    let withdrawn = read_withdrawn_funds(e, recipient, payment_token) + amount;
+    e.storage().persistent().set(&key, &withdrawn);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+//Funds raised in a payment token left for the recipients once the platform fee is taken
+//and the liquidity added
+pub fn read_distributable_funds(e: &Env, payment_token: Address) -> i128 {
+    read_raised_amount(e, payment_token.clone())
+        - read_payment_fee_amount(e, payment_token.clone())
+        - read_liquidity_provided(e, payment_token)
+}
+
+//Share of the distributable funds owed to a recipient, rounded down. The rounding dust
+//left by all the shares goes to the first recipient
+pub fn read_recipient_share(
+    e: &Env,
+    recipient: &Address,
+    payment_token: Address,
+) -> Result<i128, SaleError> {
+    let recipients = read_fund_recipients(e)?;
+    let raised = read_distributable_funds(e, payment_token);
+
+    let mut distributed = 0;
+    let mut recipient_share = None;
+    for entry in recipients.iter() {
+        let share = raised * entry.share_bps as i128 / MAX_BPS as i128;
+        distributed += share;
+        if entry.recipient == *recipient {
+            recipient_share = Some(share);
+        }
+    }
+    let share = recipient_share.ok_or(SaleError::Unauthorized)?;
+    match recipients.first() {
+        Some(first) if first.recipient == *recipient => Ok(share + raised - distributed),
+        _ => Ok(share),
+    }
+}
+
+//Released part of a recipient's share not withdrawn yet
+pub fn read_withdrawable_funds(
+    e: &Env,
+    recipient: &Address,
+    payment_token: Address,
+) -> Result<i128, SaleError> {
+    let share = read_recipient_share(e, recipient, payment_token.clone())?;
+    let released = share * read_released_bps(e) as i128 / MAX_BPS as i128;
+    Ok(released - read_withdrawn_funds(e, recipient.clone(), payment_token))
+}
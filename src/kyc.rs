@@ -0,0 +1,58 @@
+use soroban_sdk::{xdr::ToXdr, Address, BytesN, Env};
+
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, SignedVoucher, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+
+pub fn read_kyc_signer(e: &Env) -> Option<BytesN<32>> {
+    let key = DataKey::KycSigner;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_kyc_signer(e: &Env, signer: Option<BytesN<32>>) {
+    let key = DataKey::KycSigner;
+    match signer {
+        Some(signer) => e.storage().instance().set(&key, &signer),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+pub fn read_nonce_consumed(e: &Env, participant: Address, nonce: u64) -> bool {
+    let key = DataKey::ConsumedNonce(participant, nonce);
+    e.storage().persistent().has(&key)
+}
+
+pub fn write_nonce_consumed(e: &Env, participant: Address, nonce: u64) {
+    let key = DataKey::ConsumedNonce(participant, nonce);
+    e.storage().persistent().set(&key, &true);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+//The signer signs this contract's address as XDR followed by the voucher as XDR, so a
+//voucher cannot be replayed on another sale. Returns the participant's max allocation, 0
+//when uncapped. The nonce is left for the caller to consume once the purchase goes through
+pub fn check_voucher(
+    e: &Env,
+    signer: &BytesN<32>,
+    participant: &Address,
+    signed_voucher: SignedVoucher,
+) -> Result<i128, SaleError> {
+    let voucher = signed_voucher.voucher;
+    if voucher.participant != *participant {
+        return Err(SaleError::InvalidVoucher);
+    }
+    if voucher.expiry < e.ledger().timestamp() {
+        return Err(SaleError::VoucherExpired);
+    }
+    if read_nonce_consumed(e, participant.clone(), voucher.nonce) {
+        return Err(SaleError::VoucherAlreadyUsed);
+    }
+
+    let mut message = e.current_contract_address().to_xdr(e);
+    message.append(&voucher.clone().to_xdr(e));
+    //traps if the signature does not match the registered signer
+    e.crypto()
+        .ed25519_verify(signer, &message, &signed_voucher.signature);
+    Ok(voucher.max_allocation)
+}
@@ -2,9 +2,23 @@
 mod access;
 mod balances;
 mod contract;
+mod errors;
+mod events;
+mod fees;
+mod funds;
+mod kyc;
+mod liquidity;
+mod locks;
+pub mod merkle;
+mod opt_out;
+mod oracle;
+mod pause;
 mod payment_tokens;
 mod rates;
+mod release;
 mod sale_details;
 mod sale_token;
 mod storage_types;
 mod test;
+mod vesting;
+mod whitelist;
@@ -0,0 +1,65 @@
+use soroban_sdk::{contractclient, Address, Env};
+
+use crate::storage_types::{DataKey, LiquidityConfig, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+
+//The add_liquidity entrypoint of a Uniswap V2 style router, which pulls both tokens from
+//sender with transfer_from and returns the amounts used and the LP tokens minted
+#[allow(dead_code)]
+#[contractclient(name = "LiquidityRouterClient")]
+pub trait LiquidityRouter {
+    #[allow(clippy::too_many_arguments)]
+    fn add_liquidity(
+        e: Env,
+        sender: Address,
+        token_a: Address,
+        token_b: Address,
+        amount_a_desired: i128,
+        amount_b_desired: i128,
+        amount_a_min: i128,
+        amount_b_min: i128,
+        to: Address,
+        deadline: u64,
+    ) -> (i128, i128, i128);
+}
+
+pub fn read_liquidity_config(e: &Env) -> Option<LiquidityConfig> {
+    let key = DataKey::LiquidityConfig;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_liquidity_config(e: &Env, config: Option<LiquidityConfig>) {
+    let key = DataKey::LiquidityConfig;
+    match config {
+        Some(config) => e.storage().instance().set(&key, &config),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//Sale tokens deposited with the hard cap to be paired with the raised funds
+pub fn read_liquidity_reserve(e: &Env) -> i128 {
+    match read_liquidity_config(e) {
+        Some(config) => config.sale_token_reserve,
+        None => 0,
+    }
+}
+
+//Raised funds of a payment token that went into the pool
+pub fn read_liquidity_provided(e: &Env, payment_token: Address) -> i128 {
+    let key = DataKey::LiquidityProvided(payment_token);
+    if let Some(amount) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn write_liquidity_provided(e: &Env, payment_token: Address, amount: i128) {
+    let key = DataKey::LiquidityProvided(payment_token);
+    e.storage().persistent().set(&key, &amount);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
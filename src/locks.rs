@@ -0,0 +1,53 @@
+use soroban_sdk::{Address, Env};
+
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, TokenLock, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+
+pub fn read_token_lock(e: &Env, token_address: Address) -> Option<TokenLock> {
+    let key = DataKey::TokenLock(token_address);
+    let lock = e.storage().persistent().get::<DataKey, TokenLock>(&key);
+    if lock.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+    }
+    lock
+}
+
+pub fn write_token_lock(e: &Env, token_address: Address, lock: Option<TokenLock>) {
+    let key = DataKey::TokenLock(token_address);
+    match lock {
+        Some(lock) => {
+            e.storage().persistent().set(&key, &lock);
+            e.storage()
+                .persistent()
+                .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        }
+        None => e.storage().persistent().remove(&key),
+    }
+}
+
+//Add tokens already held by the contract to the lock of their token. unlock_time only applies
+//to a new lock, an existing one keeps its unlock time
+pub fn add_to_lock(
+    e: &Env,
+    token_address: Address,
+    amount: i128,
+    unlock_time: u64,
+) -> Result<TokenLock, SaleError> {
+    if amount <= 0 {
+        return Err(SaleError::InvalidConfig);
+    }
+    let lock = match read_token_lock(e, token_address.clone()) {
+        Some(lock) => TokenLock {
+            amount: lock.amount + amount,
+            unlock_time: lock.unlock_time,
+        },
+        None => TokenLock {
+            amount,
+            unlock_time,
+        },
+    };
+    write_token_lock(e, token_address, Some(lock.clone()));
+    Ok(lock)
+}
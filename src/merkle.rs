@@ -0,0 +1,77 @@
+// Merkle tree shared by the contract and off-chain tooling.
+//
+// A leaf is sha256(participant as XDR || max_allocation as 16 big endian bytes), with a
+// max_allocation of 0 meaning no per-address cap. Pairs are hashed in sorted order so a
+// proof is just the list of siblings from the leaf up, and an odd node is carried up to
+// the next level unchanged.
+//
+// The builder functions are only compiled off-chain; a backend builds the tree with an
+// `Env::default()` and hands each participant its max allocation and proof.
+
+use soroban_sdk::{xdr::ToXdr, Address, Bytes, BytesN, Env, Vec};
+
+pub fn leaf_hash(e: &Env, participant: &Address, max_allocation: i128) -> BytesN<32> {
+    let mut data = participant.clone().to_xdr(e);
+    data.extend_from_array(&max_allocation.to_be_bytes());
+    e.crypto().sha256(&data).into()
+}
+
+pub fn hash_pair(e: &Env, a: &BytesN<32>, b: &BytesN<32>) -> BytesN<32> {
+    let (a, b) = (a.to_array(), b.to_array());
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = Bytes::new(e);
+    data.extend_from_array(&first);
+    data.extend_from_array(&second);
+    e.crypto().sha256(&data).into()
+}
+
+pub fn verify_proof(e: &Env, leaf: BytesN<32>, proof: &Vec<BytesN<32>>, root: &BytesN<32>) -> bool {
+    let mut node = leaf;
+    for sibling in proof.iter() {
+        node = hash_pair(e, &node, &sibling);
+    }
+    node == *root
+}
+
+//Every level of the tree, from the leaves up to the root
+#[cfg(not(target_family = "wasm"))]
+pub fn build_levels(e: &Env, leaves: &Vec<BytesN<32>>) -> Vec<Vec<BytesN<32>>> {
+    let mut levels = Vec::new(e);
+    let mut level = leaves.clone();
+    while level.len() > 1 {
+        let mut parents = Vec::new(e);
+        let mut index = 0;
+        while index < level.len() {
+            let left = level.get_unchecked(index);
+            match level.get(index + 1) {
+                Some(right) => parents.push_back(hash_pair(e, &left, &right)),
+                None => parents.push_back(left),
+            }
+            index += 2;
+        }
+        levels.push_back(level);
+        level = parents;
+    }
+    levels.push_back(level);
+    levels
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn build_root(e: &Env, leaves: &Vec<BytesN<32>>) -> BytesN<32> {
+    let levels = build_levels(e, leaves);
+    levels.get_unchecked(levels.len() - 1).get_unchecked(0)
+}
+
+#[cfg(not(target_family = "wasm"))]
+pub fn build_proof(e: &Env, leaves: &Vec<BytesN<32>>, index: u32) -> Vec<BytesN<32>> {
+    let levels = build_levels(e, leaves);
+    let mut proof = Vec::new(e);
+    let mut index = index;
+    for level in levels.iter().take(levels.len() as usize - 1) {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push_back(sibling);
+        }
+        index /= 2;
+    }
+    proof
+}
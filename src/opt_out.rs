@@ -0,0 +1,16 @@
+use soroban_sdk::Env;
+
+use crate::storage_types::{DataKey, OptOutConfig};
+
+pub fn read_opt_out_config(e: &Env) -> Option<OptOutConfig> {
+    let key = DataKey::OptOutConfig;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_opt_out_config(e: &Env, config: Option<OptOutConfig>) {
+    let key = DataKey::OptOutConfig;
+    match config {
+        Some(config) => e.storage().instance().set(&key, &config),
+        None => e.storage().instance().remove(&key),
+    }
+}
@@ -0,0 +1,85 @@
+use soroban_sdk::{contractclient, contracttype, Address, Env, Symbol, Vec};
+
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, OracleConfig};
+use crate::vesting::MAX_BPS;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Asset {
+    Stellar(Address),
+    Other(Symbol),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PriceData {
+    pub price: i128,
+    pub timestamp: u64,
+}
+
+//The part of the SEP-40 price feed interface the sale relies on
+#[allow(dead_code)]
+#[contractclient(name = "PriceOracleClient")]
+pub trait PriceOracle {
+    fn lastprice(e: Env, asset: Asset) -> Option<PriceData>;
+    fn prices(e: Env, asset: Asset, records: u32) -> Option<Vec<PriceData>>;
+}
+
+pub fn read_oracle_config(e: &Env) -> Option<OracleConfig> {
+    let key = DataKey::OracleConfig;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_oracle_config(e: &Env, config: Option<OracleConfig>) {
+    let key = DataKey::OracleConfig;
+    match config {
+        Some(config) => e.storage().instance().set(&key, &config),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//USD price of one whole payment token in the oracle decimals. The last price is refused
+//when it is older than max_staleness or too far from the average of the recent records
+pub fn read_payment_token_price(
+    e: &Env,
+    config: &OracleConfig,
+    payment_token: Address,
+) -> Result<i128, SaleError> {
+    let oracle = PriceOracleClient::new(e, &config.oracle);
+    let asset = Asset::Stellar(payment_token);
+
+    let last_price = oracle
+        .lastprice(&asset)
+        .filter(|data| data.price > 0)
+        .ok_or(SaleError::PriceUnavailable)?;
+    if last_price.timestamp.saturating_add(config.max_staleness) < e.ledger().timestamp() {
+        return Err(SaleError::PriceUnavailable);
+    }
+
+    if config.deviation_records > 0 {
+        let records = oracle
+            .prices(&asset, &config.deviation_records)
+            .filter(|records| !records.is_empty())
+            .ok_or(SaleError::PriceUnavailable)?;
+        let mut sum: i128 = 0;
+        for record in records.iter() {
+            sum = sum
+                .checked_add(record.price)
+                .ok_or(SaleError::ArithmeticOverflow)?;
+        }
+        let average = sum / records.len() as i128;
+        if average <= 0 {
+            return Err(SaleError::PriceUnavailable);
+        }
+        let deviation_bps = (last_price.price - average)
+            .abs()
+            .checked_mul(MAX_BPS as i128)
+            .ok_or(SaleError::ArithmeticOverflow)?
+            / average;
+        if deviation_bps > config.max_deviation_bps as i128 {
+            return Err(SaleError::PriceDeviationTooHigh);
+        }
+    }
+    Ok(last_price.price)
+}
@@ -0,0 +1,21 @@
+use soroban_sdk::Env;
+
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, PausableOperation};
+
+pub fn read_is_paused(e: &Env, operation: PausableOperation) -> bool {
+    let key = DataKey::Paused(operation);
+    e.storage().instance().get(&key).unwrap_or(false)
+}
+
+pub fn write_paused(e: &Env, operation: PausableOperation, paused: bool) {
+    let key = DataKey::Paused(operation);
+    e.storage().instance().set(&key, &paused);
+}
+
+pub fn check_not_paused(e: &Env, operation: PausableOperation) -> Result<(), SaleError> {
+    if read_is_paused(e, operation) {
+        return Err(SaleError::OperationPaused);
+    }
+    Ok(())
+}
@@ -1,54 +1,118 @@
 use soroban_sdk::{Address, Env, Vec};
 
-use crate::rates::read_sale_rate;
-use crate::storage_types::{DataKey, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+use crate::errors::SaleError;
+use crate::oracle::read_oracle_config;
+use crate::rates::{read_active_sale_rate, remove_sales_rate};
+use crate::storage_types::{DataKey, PaymentTokenConfig};
 
 pub fn read_payment_count(e: &Env) -> u32 {
     let key = DataKey::PaymentTokenCount;
-    if let Some(count) = e.storage().persistent().get::<DataKey, u32>(&key) {
-        e.storage()
-            .persistent()
-            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
-        count
-    } else {
-        0
-    }
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+fn write_payment_count(e: &Env, count: u32) {
+    let key = DataKey::PaymentTokenCount;
+    e.storage().instance().set(&key, &count);
+}
+
+fn read_payment_token(e: &Env, index: u32) -> Result<Address, SaleError> {
+    let key = DataKey::PaymentToken(index);
+    e.storage()
+        .instance()
+        .get(&key)
+        .ok_or(SaleError::PaymentTokenNotFound)
 }
 
-pub fn read_payment_tokens(e: &Env) -> Vec<Address> {
-    let mut payment_tokens: Vec<Address> = Vec::new(&e);
+//Every registered payment token, enabled or not
+pub fn read_payment_tokens(e: &Env) -> Result<Vec<Address>, SaleError> {
+    let mut payment_tokens: Vec<Address> = Vec::new(e);
     let payment_count = read_payment_count(e);
     for index in 1..=payment_count {
-        let key = DataKey::PaymentToken(index);
-        let payment_token = e.storage().instance().get(&key).unwrap();
+        let payment_token = read_payment_token(e, index)?;
         payment_tokens.push_back(payment_token);
     }
-    payment_tokens
+    Ok(payment_tokens)
 }
 
+pub fn read_payment_token_config(e: &Env, payment_token: Address) -> Option<PaymentTokenConfig> {
+    let key = DataKey::PaymentTokenConfig(payment_token);
+    e.storage().instance().get(&key)
+}
+
+pub fn write_payment_token_config(e: &Env, payment_token: Address, config: &PaymentTokenConfig) {
+    let key = DataKey::PaymentTokenConfig(payment_token);
+    e.storage().instance().set(&key, config);
+}
+
+//Unknown tokens are reported as unsupported, as are disabled ones
 pub fn read_is_supported_payment_token(e: &Env, payment_token: Address) -> bool {
-    let key = DataKey::IsSupportedPayment(payment_token);
-    e.storage().instance().get(&key).unwrap()
+    match read_payment_token_config(e, payment_token) {
+        Some(config) => config.enabled,
+        None => false,
+    }
 }
 
-pub fn write_payment_token(e: &Env, token_address: Address) {
+pub fn write_payment_token(e: &Env, token_address: Address) -> Result<(), SaleError> {
+    if read_payment_token_config(e, token_address.clone()).is_some() {
+        return Err(SaleError::InvalidPaymentOptions);
+    }
+
     let index = read_payment_count(e) + 1;
     let key_token = DataKey::PaymentToken(index);
-    let key_support = DataKey::IsSupportedPayment(token_address.clone());
-    e.storage().instance().set(&key_support, &true);
     e.storage().instance().set(&key_token, &token_address);
+    write_payment_count(e, index);
+
+    let config = PaymentTokenConfig {
+        enabled: true,
+        raise_cap: 0,
+        min_contribution: 0,
+        max_contribution: 0,
+    };
+    write_payment_token_config(e, token_address, &config);
+    Ok(())
+}
+
+//The last registered token takes the place of the removed one so indexes stay contiguous
+pub fn remove_payment_token(e: &Env, token_address: Address) -> Result<(), SaleError> {
+    if read_payment_token_config(e, token_address.clone()).is_none() {
+        return Err(SaleError::UnsupportedPaymentToken);
+    }
+
+    let payment_count = read_payment_count(e);
+    for index in 1..=payment_count {
+        if read_payment_token(e, index)? == token_address {
+            let last_token = read_payment_token(e, payment_count)?;
+            e.storage()
+                .instance()
+                .set(&DataKey::PaymentToken(index), &last_token);
+            e.storage()
+                .instance()
+                .remove(&DataKey::PaymentToken(payment_count));
+            write_payment_count(e, payment_count - 1);
+            break;
+        }
+    }
+
+    e.storage()
+        .instance()
+        .remove(&DataKey::PaymentTokenConfig(token_address.clone()));
+    remove_sales_rate(e, token_address);
+    Ok(())
 }
 
-pub fn read_active_payment_tokens(e: &Env) -> Vec<Address> {
-    let mut payment_tokens: Vec<Address> = Vec::new(&e);
+//Enabled tokens with a swap rate, the ones a participant can contribute with. Every enabled
+//token is priced by the oracle in oracle mode
+pub fn read_active_payment_tokens(e: &Env) -> Result<Vec<Address>, SaleError> {
+    let mut payment_tokens: Vec<Address> = Vec::new(e);
+    let oracle_mode = read_oracle_config(e).is_some();
     let payment_count = read_payment_count(e);
     for index in 1..=payment_count {
-        let key = DataKey::PaymentToken(index);
-        let payment_token: Address = e.storage().instance().get(&key).unwrap();
-        let payment_rate = read_sale_rate(e, payment_token.clone());
-        if payment_rate > 0 {
+        let payment_token = read_payment_token(e, index)?;
+        if (oracle_mode || read_active_sale_rate(e, payment_token.clone()).is_some())
+            && read_is_supported_payment_token(e, payment_token.clone())
+        {
             payment_tokens.push_back(payment_token);
         }
     }
-    payment_tokens
+    Ok(payment_tokens)
 }
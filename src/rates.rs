@@ -1,24 +1,123 @@
 use soroban_sdk::{Address, Env};
 
-use crate::storage_types::{DataKey, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+use crate::errors::SaleError;
+use crate::oracle::{read_oracle_config, read_payment_token_price};
+use crate::sale_token::{read_token, read_token_decimals};
+use crate::storage_types::{DataKey, SwapRate, BUMP_AMOUNT, LIFETIME_THRESHOLD};
 
-pub fn read_sale_rate(e: &Env, payment_token: Address) -> u64 {
+pub fn read_sale_rate(e: &Env, payment_token: Address) -> Option<SwapRate> {
     let key = DataKey::SalesRate(payment_token);
-    // e.storage().instance().get(&key).unwrap()
-    if let Some(rate) = e.storage().persistent().get::<DataKey, u64>(&key) {
+    let rate = e.storage().persistent().get::<DataKey, SwapRate>(&key);
+    if rate.is_some() {
         e.storage()
             .persistent()
             .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
-        rate
-    } else {
-        0
     }
+    rate
 }
 
-pub fn write_sales_rate(e: &Env, payment_token: Address, rate: u64) {
+pub fn write_sales_rate(e: &Env, payment_token: Address, rate: SwapRate) {
     let key = DataKey::SalesRate(payment_token);
     e.storage().persistent().set(&key, &rate);
     e.storage()
         .persistent()
         .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
 }
+
+pub fn remove_sales_rate(e: &Env, payment_token: Address) {
+    let key = DataKey::SalesRate(payment_token);
+    e.storage().persistent().remove(&key);
+}
+
+//A zero numerator leaves the token without a price, contributions with it are refused
+pub fn read_active_sale_rate(e: &Env, payment_token: Address) -> Option<SwapRate> {
+    read_sale_rate(e, payment_token).filter(|rate| rate.numerator > 0 && rate.denominator > 0)
+}
+
+//The rate of a payment token in base units of both tokens, read once per operation since
+//oracle prices and token decimals come from other contracts
+pub struct ConversionRate {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl ConversionRate {
+    //Sale token base units bought with amount base units of the payment token, rounded
+    //down so a purchase is never allocated more than it paid for
+    pub fn purchase(&self, amount: i128) -> Result<i128, SaleError> {
+        amount
+            .checked_mul(self.numerator)
+            .map(|scaled| scaled / self.denominator)
+            .ok_or(SaleError::ArithmeticOverflow)
+    }
+
+    //Largest payment token amount whose purchase does not exceed tokens, rounded down
+    pub fn payment(&self, tokens: i128) -> Result<i128, SaleError> {
+        tokens
+            .checked_mul(self.denominator)
+            .map(|scaled| scaled / self.numerator)
+            .ok_or(SaleError::ArithmeticOverflow)
+    }
+}
+
+//The fixed swap rate of the token, or its oracle price over the sale token price in oracle
+//mode
+pub fn read_conversion_rate(e: &Env, payment_token: Address) -> Result<ConversionRate, SaleError> {
+    let (numerator, denominator) = match read_oracle_config(e) {
+        Some(config) => (
+            read_payment_token_price(e, &config, payment_token.clone())?,
+            config.sale_token_price,
+        ),
+        None => {
+            let rate =
+                read_active_sale_rate(e, payment_token.clone()).ok_or(SaleError::SwapRateNotSet)?;
+            (rate.numerator as i128, rate.denominator as i128)
+        }
+    };
+    scale_to_base_units(e, payment_token, numerator, denominator)
+}
+
+//Any other rate between the sale token and a payment token, such as a listing price
+pub fn conversion_rate(
+    e: &Env,
+    payment_token: Address,
+    rate: SwapRate,
+) -> Result<ConversionRate, SaleError> {
+    if rate.numerator == 0 || rate.denominator == 0 {
+        return Err(SaleError::InvalidPaymentOptions);
+    }
+    scale_to_base_units(
+        e,
+        payment_token,
+        rate.numerator as i128,
+        rate.denominator as i128,
+    )
+}
+
+//The decimals difference between the two tokens is moved into the numerator or the
+//denominator
+fn scale_to_base_units(
+    e: &Env,
+    payment_token: Address,
+    mut numerator: i128,
+    mut denominator: i128,
+) -> Result<ConversionRate, SaleError> {
+    let sale_decimals = read_token_decimals(e, &read_token(e)?);
+    let payment_decimals = read_token_decimals(e, &payment_token);
+
+    if sale_decimals >= payment_decimals {
+        numerator = 10i128
+            .checked_pow(sale_decimals - payment_decimals)
+            .and_then(|scale| numerator.checked_mul(scale))
+            .ok_or(SaleError::ArithmeticOverflow)?;
+    } else {
+        denominator = 10i128
+            .checked_pow(payment_decimals - sale_decimals)
+            .and_then(|scale| denominator.checked_mul(scale))
+            .ok_or(SaleError::ArithmeticOverflow)?;
+    }
+    Ok(ConversionRate {
+        numerator,
+        denominator,
+    })
+}
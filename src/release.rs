@@ -0,0 +1,98 @@
+use soroban_sdk::{Address, Env};
+
+use crate::storage_types::{DataKey, ReleaseSchedule, BUMP_AMOUNT, LIFETIME_THRESHOLD};
+use crate::vesting::MAX_BPS;
+
+pub fn read_release_schedule(e: &Env) -> Option<ReleaseSchedule> {
+    let key = DataKey::ReleaseSchedule;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_release_schedule(e: &Env, schedule: Option<ReleaseSchedule>) {
+    let key = DataKey::ReleaseSchedule;
+    match schedule {
+        Some(schedule) => e.storage().instance().set(&key, &schedule),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//Share of the raised funds the fund recipients can withdraw, frozen when the releases are
+//halted. Without a schedule everything is released at once
+pub fn read_released_bps(e: &Env) -> u32 {
+    if let Some(released_bps) = read_halted_release(e) {
+        return released_bps;
+    }
+    match read_release_schedule(e) {
+        Some(schedule) => schedule
+            .tranches
+            .iter()
+            .filter(|tranche| tranche.release_time <= e.ledger().timestamp())
+            .map(|tranche| tranche.release_bps)
+            .sum(),
+        None => MAX_BPS,
+    }
+}
+
+pub fn read_halted_release(e: &Env) -> Option<u32> {
+    let key = DataKey::ReleaseHalted;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_halted_release(e: &Env, released_bps: u32) {
+    let key = DataKey::ReleaseHalted;
+    e.storage().instance().set(&key, &released_bps);
+}
+
+pub fn read_halt_vote(e: &Env, participant: Address) -> i128 {
+    let key = DataKey::HaltVote(participant);
+    if let Some(weight) = e.storage().persistent().get::<DataKey, i128>(&key) {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+        weight
+    } else {
+        0
+    }
+}
+
+pub fn write_halt_vote(e: &Env, participant: Address, weight: i128) {
+    let key = DataKey::HaltVote(participant);
+    if weight == 0 {
+        e.storage().persistent().remove(&key);
+        return;
+    }
+    e.storage().persistent().set(&key, &weight);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
+
+pub fn read_halt_votes(e: &Env) -> i128 {
+    let key = DataKey::HaltVotes;
+    e.storage().instance().get(&key).unwrap_or(0)
+}
+
+pub fn write_halt_votes(e: &Env, votes: i128) {
+    let key = DataKey::HaltVotes;
+    e.storage().instance().set(&key, &votes);
+}
+
+//Allocation a participant kept when it reclaimed its share of the unreleased funds
+pub fn read_halt_refunded(e: &Env, participant: Address) -> Option<i128> {
+    let key = DataKey::HaltRefunded(participant);
+    let kept = e.storage().persistent().get::<DataKey, i128>(&key);
+    if kept.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+    }
+    kept
+}
+
+pub fn write_halt_refunded(e: &Env, participant: Address, kept: i128) {
+    let key = DataKey::HaltRefunded(participant);
+    e.storage().persistent().set(&key, &kept);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LIFETIME_THRESHOLD, BUMP_AMOUNT);
+}
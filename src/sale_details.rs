@@ -1,15 +1,15 @@
-use crate::storage_types::{DataKey, SalesParameter};
+use crate::access::read_administrator;
+use crate::balances::read_total_sold;
+use crate::errors::SaleError;
+use crate::storage_types::{DataKey, SaleStatus, SalesParameter, VestingSchedule};
+use crate::vesting::MAX_BPS;
 use soroban_sdk::{Address, Env};
 
 pub fn read_sales_parameters(e: &Env) -> SalesParameter {
     let key = DataKey::SaleParametersKey;
 
     if let Some(parameters) = e.storage().instance().get::<_, SalesParameter>(&key) {
-        if parameters.end_time < e.ledger().timestamp() {
-            panic!("no sales parameters as no ongoing sale")
-        } else {
-            parameters
-        }
+        parameters
     } else {
         SalesParameter {
             start_time: 0,
@@ -19,50 +19,126 @@ pub fn read_sales_parameters(e: &Env) -> SalesParameter {
             min_buy: 0,
             max_buy: 0,
             tge_time: 0,
+            vesting: VestingSchedule {
+                tge_unlock_bps: 0,
+                cliff_duration: 0,
+                vesting_duration: 0,
+                step_interval: 0,
+            },
         }
     }
 }
 
-pub fn write_sales_parameters(
-    e: &Env,
-    start_time: u64,
-    end_time: u64,
-    soft_cap: u64,
-    hard_cap: u64,
-    min_buy: u64,
-    max_buy: u64,
-    tge_time: u64,
-) {
-    if end_time <= e.ledger().timestamp()
-        || end_time < start_time
-        || soft_cap == 0
-        || hard_cap < soft_cap
-        || max_buy < min_buy
-        || tge_time < end_time
+pub fn write_sales_parameters(e: &Env, parameters: SalesParameter) -> Result<(), SaleError> {
+    let vesting = &parameters.vesting;
+    if parameters.end_time <= e.ledger().timestamp()
+        || parameters.end_time < parameters.start_time
+        || parameters.soft_cap == 0
+        || parameters.hard_cap < parameters.soft_cap
+        || parameters.max_buy < parameters.min_buy
+        || parameters.tge_time < parameters.end_time
+        || vesting.tge_unlock_bps > MAX_BPS
+        || vesting.step_interval > vesting.vesting_duration
     {
-        panic!("invalid parameter(s) entered!")
+        return Err(SaleError::InvalidConfig);
     }
 
-    let parameters = SalesParameter {
-        start_time,
-        end_time,
-        soft_cap,
-        hard_cap,
-        min_buy,
-        max_buy,
-        tge_time,
-    };
-
     let key = DataKey::SaleParametersKey;
     e.storage().instance().set(&key.clone(), &parameters);
+    write_sale_status(e, SaleStatus::Upcoming)
+}
+
+//The stored status only moves on explicit transitions, the time based phases
+//(Upcoming -> Active -> Ended) are derived from the sales parameters
+pub fn read_sale_status(e: &Env) -> SaleStatus {
+    let key = DataKey::SaleStatusKey;
+    let status = e
+        .storage()
+        .instance()
+        .get::<_, SaleStatus>(&key)
+        .unwrap_or(SaleStatus::NotConfigured);
+
+    match status {
+        SaleStatus::Upcoming | SaleStatus::Active => {
+            let parameters = read_sales_parameters(e);
+            let now = e.ledger().timestamp();
+            if now < parameters.start_time {
+                SaleStatus::Upcoming
+            } else if now <= parameters.end_time {
+                SaleStatus::Active
+            } else {
+                SaleStatus::Ended
+            }
+        }
+        _ => status,
+    }
+}
+
+pub fn write_sale_status(e: &Env, status: SaleStatus) -> Result<(), SaleError> {
+    let current_status = read_sale_status(e);
+    if !is_valid_transition(current_status, status) {
+        return Err(SaleError::InvalidStatusTransition);
+    }
+    let key = DataKey::SaleStatusKey;
+    e.storage().instance().set(&key, &status);
+    Ok(())
+}
+
+fn is_valid_transition(from: SaleStatus, to: SaleStatus) -> bool {
+    matches!(
+        (from, to),
+        (SaleStatus::NotConfigured, SaleStatus::Upcoming)
+            | (SaleStatus::Active, SaleStatus::Ended)
+            | (SaleStatus::Ended, SaleStatus::Succeeded)
+            | (SaleStatus::Ended, SaleStatus::Failed)
+            | (SaleStatus::Succeeded, SaleStatus::Finalized)
+            | (SaleStatus::Upcoming, SaleStatus::Cancelled)
+            | (SaleStatus::Active, SaleStatus::Cancelled)
+            | (SaleStatus::Ended, SaleStatus::Cancelled)
+    )
+}
+
+//Outcome of a sale whose contribution window is closed, based on the soft cap
+pub fn read_sale_outcome(e: &Env) -> SaleStatus {
+    let soft_cap = read_sales_parameters(e).soft_cap as i128;
+    if read_total_sold(e) >= soft_cap {
+        SaleStatus::Succeeded
+    } else {
+        SaleStatus::Failed
+    }
+}
+
+pub fn read_partial_fill(e: &Env) -> bool {
+    let key = DataKey::PartialFill;
+    e.storage().instance().get(&key).unwrap_or(false)
+}
+
+pub fn write_partial_fill(e: &Env, enabled: bool) {
+    let key = DataKey::PartialFill;
+    e.storage().instance().set(&key, &enabled);
 }
 
-pub fn write_fund_recipient(e: &Env, recipient: Address) {
-    let key = DataKey::FundsRecipient;
-    e.storage().instance().set(&key, &recipient);
+pub fn read_oversubscription(e: &Env) -> bool {
+    let key = DataKey::Oversubscription;
+    e.storage().instance().get(&key).unwrap_or(false)
 }
 
-pub fn read_fund_recipient(e: &Env) -> Address {
-    let key = DataKey::FundsRecipient;
-    e.storage().instance().get(&key).unwrap()
+pub fn write_oversubscription(e: &Env, enabled: bool) {
+    let key = DataKey::Oversubscription;
+    e.storage().instance().set(&key, &enabled);
+}
+
+pub fn write_project_wallet(e: &Env, wallet: Address) {
+    let key = DataKey::ProjectWallet;
+    e.storage().instance().set(&key, &wallet);
+}
+
+//Unsold sale tokens go back to the project wallet, the admin unless configured
+pub fn read_project_wallet(e: &Env) -> Result<Address, SaleError> {
+    let key = DataKey::ProjectWallet;
+    if let Some(wallet) = e.storage().instance().get::<_, Address>(&key) {
+        Ok(wallet)
+    } else {
+        read_administrator(e)
+    }
 }
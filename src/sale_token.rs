@@ -1,5 +1,6 @@
 use soroban_sdk::{token, Address, Env};
 
+use crate::errors::SaleError;
 use crate::storage_types::DataKey;
 
 // pub fn sales_token_has_been_set(e: &Env) -> bool {
@@ -7,9 +8,12 @@ use crate::storage_types::DataKey;
 //     e.storage().instance().has(&key)
 // }
 
-pub fn read_token(e: &Env) -> Address {
+pub fn read_token(e: &Env) -> Result<Address, SaleError> {
     let key = DataKey::Token;
-    e.storage().instance().get(&key).unwrap()
+    e.storage()
+        .instance()
+        .get(&key)
+        .ok_or(SaleError::SaleTokenNotSet)
 }
 
 pub fn write_token(e: &Env, token_address: &Address) {
@@ -17,6 +21,10 @@ pub fn write_token(e: &Env, token_address: &Address) {
     e.storage().instance().set(&key, token_address);
 }
 
+pub fn read_token_decimals(env: &Env, token_address: &Address) -> u32 {
+    token::Client::new(env, token_address).decimals()
+}
+
 pub fn take_token(env: &Env, token_address: &Address, from: &Address, transfer_amount: i128) {
     let token = token::Client::new(env, token_address);
     let contract_address = env.current_contract_address();
@@ -1,4 +1,4 @@
-use soroban_sdk::{contracttype, Address};
+use soroban_sdk::{contracttype, Address, BytesN, Vec};
 
 pub(crate) const DAY_IN_LEDGERS: u32 = 17280;
 pub(crate) const BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
@@ -14,25 +14,237 @@ pub struct SalesParameter {
     pub min_buy: u64,
     pub max_buy: u64,
     pub tge_time: u64,
+    pub vesting: VestingSchedule,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PaymentTokenConfig {
+    pub enabled: bool,   //disabled tokens are kept for refunds and withdrawals only
+    pub raise_cap: i128, //max total raised in this token, 0 for no cap
+    pub min_contribution: i128, //min amount per contribution
+    pub max_contribution: i128, //max total contribution per participant, 0 for no cap
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct PaymentOption {
+    pub token: Address,
+    pub rate: SwapRate,
+}
+
+//numerator whole sale tokens are sold for denominator whole payment tokens, the decimals
+//of both tokens are applied when converting base units
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SwapRate {
+    pub numerator: u64,
+    pub denominator: u64,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct FundRecipient {
+    pub recipient: Address,
+    pub share_bps: u32, //the shares of all recipients sum to 10000
+}
+
+//What contribute would do with an amount, as returned by preview_contribute
+#[derive(Clone)]
+#[contracttype]
+pub struct ContributionPreview {
+    pub amount_accepted: i128, //payment taken, less than the amount on a partial fill
+    pub tokens_purchased: i128,
+    pub remaining_personal_cap: i128, //tokens the participant can still buy under max_buy
+    pub remaining_hard_cap: i128,
+    pub error_code: u32, //the SaleError contribute would fail with, 0 when it succeeds
+}
+
+//In oracle mode the rate of each payment token is its USD price over the sale token price
+#[derive(Clone)]
+#[contracttype]
+pub struct OracleConfig {
+    pub oracle: Address,        //SEP-40 price feed quoted in USD
+    pub sale_token_price: i128, //USD price of one whole sale token, in the oracle decimals
+    pub max_staleness: u64,     //max age in seconds of the last price
+    pub max_deviation_bps: u32, //max distance of the last price from the recent average
+    pub deviation_records: u32, //number of recent prices averaged, 0 to skip the check
+}
+
+//Everything needed to open a sale, set atomically by initialize
+#[derive(Clone)]
+#[contracttype]
+pub struct SaleConfig {
+    pub sale_token: Address,
+    pub payment_options: Vec<PaymentOption>,
+    pub fund_recipient: Address,
+    pub parameters: SalesParameter,
+}
+
+//Liquidity added to an AMM pool on finalization, it cannot change once the sale is
+//configured since its sale tokens are deposited along with the hard cap
+#[derive(Clone)]
+#[contracttype]
+pub struct LiquidityConfig {
+    pub router: Address,
+    pub payment_token: Address,   //paired with the sale token in the pool
+    pub liquidity_bps: u32,       //share of the funds raised in payment_token, after the fee
+    pub listing_rate: SwapRate,   //sale tokens per payment token in the pool
+    pub sale_token_reserve: i128, //max sale tokens paired with the funds
+    pub max_slippage_bps: u32,    //how much less of each token the router may use
+    pub lp_recipient: Address,    //receives the LP tokens when they are not locked
+    pub lp_token: Address,
+    pub lp_unlock_time: u64, //the LP tokens stay locked in the contract until then, 0 to skip
+}
+
+//Raised funds released to the fund recipients in tranches rather than all at once. The
+//participants can vote to halt the releases and reclaim what is not released yet
+#[derive(Clone)]
+#[contracttype]
+pub struct ReleaseSchedule {
+    pub tranches: Vec<ReleaseTranche>, //in release time order, their shares sum to 10000
+    pub halt_quorum_bps: u32,          //share of the tokens sold whose buyers must vote to halt
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct ReleaseTranche {
+    pub release_time: u64,
+    pub release_bps: u32, //share of the raised funds withdrawable from release_time
+}
+
+//Participants can withdraw their contribution in a payment token until the deadline, less
+//a penalty sent to the treasury, and give up the tokens purchased with it
+#[derive(Clone)]
+#[contracttype]
+pub struct OptOutConfig {
+    pub deadline: u64, //last timestamp of the opt-out window, which also closes with the sale
+    pub penalty_bps: u32, //share of the withdrawn contribution kept as a penalty
+    pub treasury: Address,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct TokenLock {
+    pub amount: i128,
+    pub unlock_time: u64,
+}
+
+//Fee taken by the launchpad, it cannot change once the sale is configured
+#[derive(Clone)]
+#[contracttype]
+pub struct PlatformFee {
+    pub treasury: Address,
+    pub fee_bps: u32,            //share of the funds raised in every payment token
+    pub sale_token_fee_bps: u32, //share of the sale tokens sold, deposited with the hard cap
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct VestingSchedule {
+    pub tge_unlock_bps: u32, //share of the purchase released at tge_time, in basis points
+    pub cliff_duration: u64, //time after tge_time before the linear vesting starts
+    pub vesting_duration: u64, //time over which the rest is released linearly
+    pub step_interval: u64,  //release granularity, 0 for a continuous release
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum SaleStatus {
+    NotConfigured, //no sales parameters have been set yet
+    Upcoming,      //parameters set, waiting for the start time
+    Active,        //contributions are accepted
+    Ended,         //contribution window closed, outcome not settled yet
+    Succeeded,     //soft cap reached, purchased tokens can be claimed
+    Failed,        //soft cap not reached, contributions can be refunded
+    Cancelled,     //sale aborted by the admin
+    Finalized,     //first withdrawal made, recipients frozen, claims and later withdrawals go on
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct WhitelistProof {
+    pub max_allocation: i128, //allocation cap encoded in the leaf, 0 for no cap
+    pub proof: Vec<BytesN<32>>, //sibling hashes from the leaf up to the root
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct AllocationVoucher {
+    pub participant: Address,
+    pub max_allocation: i128, //0 for no cap
+    pub expiry: u64,          //timestamp after which the voucher is rejected
+    pub nonce: u64,           //single use, per participant
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct SignedVoucher {
+    pub voucher: AllocationVoucher,
+    pub signature: BytesN<64>, //ed25519 signature from the KYC signer key
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Owner,        //the admin, grants and revokes every other role
+    SaleOperator, //configures the sale token, payment tokens, rates and parameters
+    Finance,      //sets the wallets receiving the raised funds and unsold tokens
+    Pauser,       //pauses and unpauses operations
+    Compliance,   //manages the whitelist root and the KYC signer
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum PausableOperation {
+    Contribute,
+    Claim,
+    Refund,
+    Withdraw,
 }
 
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     Admin,                                     //Admin of the contract
+    PendingAdmin,                              //Admin proposed by the current admin, until accepted
+    Role(Role, Address),                       //roles granted by the admin
+    Paused(PausableOperation),                 //whether an operation is paused by the admin
     Token,                                     // Token to be sold
     SalesRate(Address), //The rate (swap ratio) of the token sale with respect to the purchase token
     PaymentToken(u32),  // Supported Payment tokens
-    IsSupportedPayment(Address), //Supported payment token validation
-    PaymentTokenCount,  //Number of supported payment tokens
+    PaymentTokenConfig(Address), //Status and limits of a registered payment token
+    PaymentTokenCount,  //Number of registered payment tokens
     ParticipantContribution(Address, Address), //Amount spent by each participants (the keys are the participants address and the payment token address)
     TotalContribution(Address),                //Total funds raised (the key is token address)
     ParticipantsCount,                         //the total number of unique participants
     SaleParametersKey,                         // stores all the sales parameters
-    FundsRecipient,                            //Wallet that received or claims the funds
+    SaleStatusKey,                             // stores the lifecycle status of the sale
+    FundRecipients, //Wallets sharing the raised funds, with their shares in basis points
+    FundsWithdrawn(Address, Address), //Funds already withdrawn by a recipient in a payment token
+    PlatformFee,    //fee of the launchpad, none when unset
+    FeeCollected(Address), //fee sent to the platform treasury in each token
+    LiquidityConfig, //pool seeded on finalization, none when unset
+    LiquidityProvided(Address), //raised funds of a payment token added to the pool
+    TokenLock(Address), //tokens held by the contract until an unlock time
+    ReleaseSchedule, //tranches the raised funds are released in, all at once when unset
+    ReleaseHalted,  //share of the raised funds released when the participants halted the releases
+    HaltVote(Address), //purchase weight of a participant voting to halt the releases
+    HaltVotes,      //total weight of the halt votes
+    HaltRefunded(Address), //allocation kept by a participant that reclaimed its share of the unreleased funds
+    ProjectWallet,         //Wallet that receives the unsold sale tokens
+    PartialFill,           //whether contributions crossing the hard cap are partially filled
+    Oversubscription,      //whether contributions above the hard cap are allocated pro-rata
+    MerkleRoot,            //root of the whitelist, contributions are open to anyone when unset
+    KycSigner,             //ed25519 public key that signs allocation vouchers
+    OracleConfig,          //price feed the rates are derived from, fixed rates are used when unset
+    ConsumedNonce(Address, u64), //voucher nonces already used by a participant
     AmountPurchased(Address), //the amount of tokens purchased by a participants (amount contributed*rate)
-    Trefund(Address), //time until participants can withdraw contribution and opt out of the sale
+    AmountClaimed(Address),   //the amount of purchased tokens already claimed by a participant
+    ExcessRefunded(Address),  //whether the unfilled part of an oversubscribed purchase was refunded
+    TokenPurchase(Address, Address), //tokens purchased by a participant with a payment token
+    OptOutConfig, //window during which participants can withdraw their contribution, none when unset
     //sales hardcap
     TotalTokensSold, //Amount of tokens already sold
-    TokensRemaining, // Amount of tokens left
+    TokensRemaining, // Amount of unsold tokens returned to the project on finalization
 }
@@ -1,2 +1,1545 @@
 #![cfg(test)]
+//Amounts are written as whole tokens followed by the 7 decimal places
+#![allow(clippy::inconsistent_digit_grouping)]
 extern crate std;
+
+use crate::balances::read_participants_count;
+use crate::contract::{TokenSale, TokenSaleClient};
+use crate::errors::SaleError;
+use crate::merkle;
+use crate::oracle::{Asset, PriceData};
+use crate::storage_types::{
+    AllocationVoucher, FundRecipient, LiquidityConfig, OptOutConfig, OracleConfig,
+    PausableOperation, PaymentOption, PlatformFee, ReleaseSchedule, ReleaseTranche, Role,
+    SaleConfig, SaleStatus, SalesParameter, SignedVoucher, SwapRate, VestingSchedule,
+    WhitelistProof,
+};
+use crate::vesting;
+
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Ledger};
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::{
+    contract, contractimpl, symbol_short, token, vec, Address, BytesN, Env, IntoVal, Symbol, Vec,
+};
+
+//SEP-40 price feed serving the prices pushed by the tests, most recent first
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(e: Env, asset: Asset, price: i128, timestamp: u64) {
+        let mut records: Vec<PriceData> =
+            e.storage().instance().get(&asset).unwrap_or(Vec::new(&e));
+        records.push_front(PriceData { price, timestamp });
+        e.storage().instance().set(&asset, &records);
+    }
+
+    pub fn lastprice(e: Env, asset: Asset) -> Option<PriceData> {
+        let records: Vec<PriceData> = e.storage().instance().get(&asset)?;
+        records.first()
+    }
+
+    pub fn prices(e: Env, asset: Asset, records: u32) -> Option<Vec<PriceData>> {
+        let all_records: Vec<PriceData> = e.storage().instance().get(&asset)?;
+        Some(all_records.slice(0..records.min(all_records.len())))
+    }
+}
+
+//Uniswap V2 style router keeping the tokens it pulls and minting one LP token per unit of
+//token_b
+#[contract]
+pub struct MockRouter;
+
+#[contractimpl]
+impl MockRouter {
+    pub fn set_lp_token(e: Env, lp_token: Address) {
+        e.storage()
+            .instance()
+            .set(&symbol_short!("lp_token"), &lp_token);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_liquidity(
+        e: Env,
+        sender: Address,
+        token_a: Address,
+        token_b: Address,
+        amount_a_desired: i128,
+        amount_b_desired: i128,
+        _amount_a_min: i128,
+        _amount_b_min: i128,
+        to: Address,
+        _deadline: u64,
+    ) -> (i128, i128, i128) {
+        let router = e.current_contract_address();
+        token::Client::new(&e, &token_a).transfer_from(
+            &router,
+            &sender,
+            &router,
+            &amount_a_desired,
+        );
+        token::Client::new(&e, &token_b).transfer_from(
+            &router,
+            &sender,
+            &router,
+            &amount_b_desired,
+        );
+
+        let lp_token: Address = e
+            .storage()
+            .instance()
+            .get(&symbol_short!("lp_token"))
+            .unwrap();
+        token::StellarAssetClient::new(&e, &lp_token).mint(&to, &amount_b_desired);
+        (amount_a_desired, amount_b_desired, amount_b_desired)
+    }
+}
+
+//SEP-41 token with any number of decimals, Stellar asset contracts always have 7
+#[contract]
+pub struct MockToken;
+
+#[contractimpl]
+impl MockToken {
+    pub fn set_decimals(e: Env, decimals: u32) {
+        e.storage()
+            .instance()
+            .set(&symbol_short!("decimals"), &decimals);
+    }
+
+    pub fn mint(e: Env, to: Address, amount: i128) {
+        let balance = Self::balance(e.clone(), to.clone());
+        e.storage().persistent().set(&to, &(balance + amount));
+    }
+
+    pub fn balance(e: Env, id: Address) -> i128 {
+        e.storage().persistent().get(&id).unwrap_or(0)
+    }
+
+    pub fn transfer(e: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        let balance = Self::balance(e.clone(), from.clone());
+        assert!(balance >= amount, "balance too low");
+        e.storage().persistent().set(&from, &(balance - amount));
+        Self::mint(e, to, amount);
+    }
+
+    pub fn decimals(e: Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&symbol_short!("decimals"))
+            .unwrap()
+    }
+}
+
+struct TestSale<'a> {
+    sale: TokenSaleClient<'a>,
+    admin: Address,
+    sale_token: Address,
+    payment_token: Address,
+    participant: Address,
+}
+
+//Everything passed to initialize
+struct InitArgs {
+    config: SaleConfig,
+    platform_fee: Option<PlatformFee>,
+    liquidity: Option<LiquidityConfig>,
+    release: Option<ReleaseSchedule>,
+}
+
+//A sale of a 7 decimals token at 2 tokens per unit of a 7 decimals payment token, active
+//from timestamp 2_000 to 10_000 with the ledger at 3_000. The initialize arguments can be
+//adjusted knowing the admin and the payment token
+fn create_custom_sale<'a>(
+    e: &'a Env,
+    configure: impl FnOnce(&Address, &Address, &mut InitArgs),
+) -> TestSale<'a> {
+    e.mock_all_auths();
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_000);
+
+    let admin = Address::generate(e);
+    let participant = Address::generate(e);
+    let sale_token = e
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    let payment_token = e
+        .register_stellar_asset_contract_v2(admin.clone())
+        .address();
+    token::StellarAssetClient::new(e, &sale_token).mint(&admin, &2_000_000_0000000);
+    token::StellarAssetClient::new(e, &payment_token).mint(&participant, &1_000_0000000);
+
+    let config = SaleConfig {
+        sale_token: sale_token.clone(),
+        payment_options: vec![
+            e,
+            PaymentOption {
+                token: payment_token.clone(),
+                rate: SwapRate {
+                    numerator: 2,
+                    denominator: 1,
+                },
+            },
+        ],
+        fund_recipient: admin.clone(),
+        parameters: SalesParameter {
+            start_time: 2_000,
+            end_time: 10_000,
+            soft_cap: 1_0000000,
+            hard_cap: 1_000_000_0000000,
+            min_buy: 0,
+            max_buy: 1_000_000_0000000,
+            tge_time: 10_000,
+            vesting: VestingSchedule {
+                tge_unlock_bps: 10_000,
+                cliff_duration: 0,
+                vesting_duration: 0,
+                step_interval: 0,
+            },
+        },
+    };
+    let mut args = InitArgs {
+        config,
+        platform_fee: None,
+        liquidity: None,
+        release: None,
+    };
+    configure(&admin, &payment_token, &mut args);
+
+    //the sale is deployed by the admin, who proves it with the salt
+    let salt = BytesN::from_array(e, &[1; 32]);
+    let sale_address = e
+        .deployer()
+        .with_address(admin.clone(), salt.clone())
+        .deployed_address();
+    let sale = TokenSaleClient::new(e, &e.register_contract(Some(&sale_address), TokenSale));
+    sale.initialize(
+        &admin,
+        &salt,
+        &args.config,
+        &args.platform_fee,
+        &args.liquidity,
+        &args.release,
+    );
+
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+    TestSale {
+        sale,
+        admin,
+        sale_token,
+        payment_token,
+        participant,
+    }
+}
+
+//With a router, half of the raise is added to its pool at a listing rate of 1, and the LP
+//tokens are locked until lp_unlock_time if it is not zero
+fn create_sale<'a>(e: &'a Env, router: Option<(&Address, u64)>) -> TestSale<'a> {
+    create_custom_sale(e, |admin, payment_token, args| {
+        args.liquidity = router.map(|(router, lp_unlock_time)| LiquidityConfig {
+            router: router.clone(),
+            payment_token: payment_token.clone(),
+            liquidity_bps: 5_000,
+            listing_rate: SwapRate {
+                numerator: 1,
+                denominator: 1,
+            },
+            sale_token_reserve: 1_000_0000000,
+            max_slippage_bps: 100,
+            lp_recipient: admin.clone(),
+            lp_token: e
+                .register_stellar_asset_contract_v2(router.clone())
+                .address(),
+            lp_unlock_time,
+        });
+    })
+}
+
+struct OracleSale<'a> {
+    sale: TokenSaleClient<'a>,
+    oracle: MockOracleClient<'a>,
+    payment_token: Address,
+    participant: Address,
+}
+
+//The sale token is priced at 0.05 USD and the payment token by the mock oracle
+fn create_oracle_sale(e: &Env) -> OracleSale<'_> {
+    let setup = create_sale(e, None);
+    let oracle = MockOracleClient::new(e, &e.register_contract(None, MockOracle));
+    setup.sale.set_price_oracle(
+        &setup.admin,
+        &Some(OracleConfig {
+            oracle: oracle.address.clone(),
+            sale_token_price: 500_000,
+            max_staleness: 600,
+            max_deviation_bps: 1_000,
+            deviation_records: 3,
+        }),
+    );
+
+    OracleSale {
+        sale: setup.sale,
+        oracle,
+        payment_token: setup.payment_token,
+        participant: setup.participant,
+    }
+}
+
+#[test]
+fn test_oracle_priced_contribution() {
+    let e = Env::default();
+    let setup = create_oracle_sale(&e);
+    let asset = Asset::Stellar(setup.payment_token.clone());
+    setup.oracle.set_price(&asset, &1_000_000, &2_800);
+    setup.oracle.set_price(&asset, &1_000_000, &2_900);
+
+    //100 payment tokens at 0.1 USD buy 200 sale tokens at 0.05 USD
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    assert_eq!(
+        setup
+            .sale
+            .get_participant_total_purchase(&setup.participant),
+        200_0000000
+    );
+}
+
+#[test]
+fn test_oracle_stale_price() {
+    let e = Env::default();
+    let setup = create_oracle_sale(&e);
+    let asset = Asset::Stellar(setup.payment_token.clone());
+    setup.oracle.set_price(&asset, &1_000_000, &2_000);
+
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &100_0000000,
+            &None,
+            &None,
+        ),
+        Err(Ok(SaleError::PriceUnavailable))
+    );
+}
+
+#[test]
+fn test_oracle_price_deviation() {
+    let e = Env::default();
+    let setup = create_oracle_sale(&e);
+    let asset = Asset::Stellar(setup.payment_token.clone());
+    setup.oracle.set_price(&asset, &1_000_000, &2_800);
+    setup.oracle.set_price(&asset, &1_000_000, &2_900);
+    setup.oracle.set_price(&asset, &1_500_000, &3_000);
+
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &100_0000000,
+            &None,
+            &None,
+        ),
+        Err(Ok(SaleError::PriceDeviationTooHigh))
+    );
+}
+
+#[test]
+fn test_liquidity_added_on_finalize() {
+    let e = Env::default();
+    let router = MockRouterClient::new(&e, &e.register_contract(None, MockRouter));
+    let setup = create_sale(&e, Some((&router.address, 0)));
+    let lp_token = setup.sale.get_liquidity_config().unwrap().lp_token;
+    router.set_lp_token(&lp_token);
+
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+
+    //half of the 100 payment tokens raised, paired 1 for 1 with sale tokens
+    assert_eq!(
+        setup.sale.get_liquidity_provided(&setup.payment_token),
+        50_0000000
+    );
+    assert_eq!(
+        token::Client::new(&e, &setup.payment_token).balance(&router.address),
+        50_0000000
+    );
+    assert_eq!(
+        token::Client::new(&e, &setup.sale_token).balance(&router.address),
+        50_0000000
+    );
+    assert_eq!(
+        token::Client::new(&e, &lp_token).balance(&setup.admin),
+        50_0000000
+    );
+    assert_eq!(
+        setup
+            .sale
+            .get_withdrawable_funds(&setup.admin, &setup.payment_token),
+        50_0000000
+    );
+}
+
+#[test]
+fn test_liquidity_locked_on_finalize() {
+    let e = Env::default();
+    let router = MockRouterClient::new(&e, &e.register_contract(None, MockRouter));
+    let setup = create_sale(&e, Some((&router.address, 20_000)));
+    let lp_token = setup.sale.get_liquidity_config().unwrap().lp_token;
+    router.set_lp_token(&lp_token);
+
+    //nobody can open the LP lock before finalize, and only the owner can lock
+    token::StellarAssetClient::new(&e, &lp_token).mint(&setup.admin, &1);
+    token::StellarAssetClient::new(&e, &lp_token).mint(&setup.participant, &1);
+    assert_eq!(
+        setup
+            .sale
+            .try_lock_tokens(&setup.admin, &lp_token, &1, &10_500),
+        Err(Ok(SaleError::InvalidConfig))
+    );
+    assert_eq!(
+        setup
+            .sale
+            .try_lock_tokens(&setup.participant, &lp_token, &1, &u64::MAX),
+        Err(Ok(SaleError::Unauthorized))
+    );
+
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+
+    //adding to the lock keeps its unlock time
+    setup
+        .sale
+        .lock_tokens(&setup.admin, &lp_token, &1, &u64::MAX);
+    let lock = setup.sale.get_lock(&lp_token).unwrap();
+    assert_eq!(lock.amount, 50_0000001);
+    assert_eq!(lock.unlock_time, 20_000);
+    assert_eq!(
+        token::Client::new(&e, &lp_token).balance(&setup.sale.address),
+        50_0000001
+    );
+
+    assert_eq!(
+        setup.sale.try_extend_lock(&setup.admin, &lp_token, &15_000),
+        Err(Ok(SaleError::LockShortened))
+    );
+    assert_eq!(
+        setup
+            .sale
+            .try_withdraw_locked(&setup.admin, &lp_token, &setup.admin),
+        Err(Ok(SaleError::TokensLocked))
+    );
+
+    e.ledger().with_mut(|ledger| ledger.timestamp = 20_000);
+    setup
+        .sale
+        .withdraw_locked(&setup.admin, &lp_token, &setup.admin);
+    assert_eq!(
+        token::Client::new(&e, &lp_token).balance(&setup.admin),
+        50_0000001
+    );
+    assert!(setup.sale.get_lock(&lp_token).is_none());
+}
+
+#[test]
+fn test_roles() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let pauser = Address::generate(&e);
+    assert!(setup.sale.has_role(&Role::Pauser, &setup.admin));
+    assert!(!setup.sale.has_role(&Role::Pauser, &pauser));
+    assert_eq!(
+        setup
+            .sale
+            .try_pause(&pauser, &PausableOperation::Contribute),
+        Err(Ok(SaleError::Unauthorized))
+    );
+
+    //only the admin grants, and a role gives access to its own operations only
+    setup.sale.grant_role(&Role::Pauser, &pauser);
+    assert_eq!(e.auths()[0].0, setup.admin);
+    assert!(setup.sale.has_role(&Role::Pauser, &pauser));
+    setup.sale.pause(&pauser, &PausableOperation::Contribute);
+    assert_eq!(
+        setup.sale.try_set_merkle_root(&pauser, &None),
+        Err(Ok(SaleError::Unauthorized))
+    );
+
+    setup.sale.revoke_role(&Role::Pauser, &pauser);
+    assert_eq!(e.auths()[0].0, setup.admin);
+    assert_eq!(
+        setup
+            .sale
+            .try_unpause(&pauser, &PausableOperation::Contribute),
+        Err(Ok(SaleError::Unauthorized))
+    );
+
+    //the holder renounces on its own
+    setup.sale.grant_role(&Role::Pauser, &pauser);
+    setup.sale.renounce_role(&Role::Pauser, &pauser);
+    assert_eq!(e.auths()[0].0, pauser);
+    assert!(!setup.sale.has_role(&Role::Pauser, &pauser));
+
+    assert_eq!(
+        setup.sale.try_grant_role(&Role::Owner, &pauser),
+        Err(Ok(SaleError::InvalidRole))
+    );
+    assert_eq!(
+        setup.sale.try_renounce_role(&Role::Owner, &setup.admin),
+        Err(Ok(SaleError::InvalidRole))
+    );
+}
+
+#[test]
+fn test_admin_transfer() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let new_admin = Address::generate(&e);
+    assert_eq!(
+        setup.sale.try_accept_admin(),
+        Err(Ok(SaleError::NoPendingAdmin))
+    );
+
+    setup.sale.propose_admin(&new_admin);
+    assert_eq!(e.auths()[0].0, setup.admin);
+    assert_eq!(setup.sale.get_pending_admin(), Some(new_admin.clone()));
+    assert_eq!(setup.sale.get_admin(), setup.admin);
+
+    //the transfer completes with the authorization of the new admin alone
+    setup.sale.accept_admin();
+    assert_eq!(
+        e.auths(),
+        std::vec![(
+            new_admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    setup.sale.address.clone(),
+                    Symbol::new(&e, "accept_admin"),
+                    ().into_val(&e),
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+    assert_eq!(setup.sale.get_admin(), new_admin);
+    assert_eq!(setup.sale.get_pending_admin(), None);
+    assert!(!setup.sale.has_role(&Role::Owner, &setup.admin));
+
+    //a proposal can be withdrawn by the admin before it is accepted
+    setup.sale.propose_admin(&setup.admin);
+    setup.sale.cancel_admin_transfer();
+    assert_eq!(e.auths()[0].0, new_admin);
+    assert_eq!(setup.sale.get_pending_admin(), None);
+    assert_eq!(
+        setup.sale.try_accept_admin(),
+        Err(Ok(SaleError::NoPendingAdmin))
+    );
+    assert_eq!(
+        setup.sale.try_cancel_admin_transfer(),
+        Err(Ok(SaleError::NoPendingAdmin))
+    );
+}
+
+#[test]
+fn test_pause_blocks_operations() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_500);
+    setup.sale.set_opt_out_config(
+        &setup.admin,
+        &Some(OptOutConfig {
+            deadline: 5_000,
+            penalty_bps: 1_000,
+            treasury: setup.admin.clone(),
+        }),
+    );
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+    let other = Address::generate(&e);
+    token::StellarAssetClient::new(&e, &setup.payment_token).mint(&other, &10_0000000);
+    let paused = Err(Ok(SaleError::OperationPaused));
+
+    setup
+        .sale
+        .pause(&setup.admin, &PausableOperation::Contribute);
+    assert!(setup.sale.is_paused(&PausableOperation::Contribute));
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &100_0000000,
+            &None,
+            &None
+        ),
+        paused
+    );
+    assert_eq!(
+        setup
+            .sale
+            .try_contribute_with_min_out(
+                &setup.participant,
+                &setup.payment_token,
+                &100_0000000,
+                &0,
+                &3_000,
+                &None,
+                &None
+            )
+            .map(|_| Ok(())),
+        paused
+    );
+    setup
+        .sale
+        .unpause(&setup.admin, &PausableOperation::Contribute);
+    for participant in [&setup.participant, &other] {
+        setup
+            .sale
+            .contribute(participant, &setup.payment_token, &10_0000000, &None, &None);
+    }
+
+    //the refund entrypoints only fail on the sale state once unpaused
+    setup.sale.pause(&setup.admin, &PausableOperation::Refund);
+    assert_eq!(setup.sale.try_opt_out(&other, &setup.payment_token), paused);
+    assert_eq!(setup.sale.try_claim_refund(&setup.participant), paused);
+    assert_eq!(setup.sale.try_claim_halt_refund(&setup.participant), paused);
+    setup.sale.unpause(&setup.admin, &PausableOperation::Refund);
+    setup.sale.opt_out(&other, &setup.payment_token);
+    assert_eq!(
+        setup.sale.try_claim_refund(&setup.participant),
+        Err(Ok(SaleError::SaleNotOver))
+    );
+    assert_eq!(
+        setup.sale.try_claim_halt_refund(&setup.participant),
+        Err(Ok(SaleError::ReleaseNotHalted))
+    );
+
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+    setup.sale.pause(&setup.admin, &PausableOperation::Claim);
+    assert_eq!(
+        setup.sale.try_claim_purchased_tokens(&setup.participant),
+        paused
+    );
+    assert_eq!(setup.sale.try_claim(&setup.participant), paused);
+    setup.sale.unpause(&setup.admin, &PausableOperation::Claim);
+    setup.sale.claim(&setup.participant);
+    assert_eq!(
+        setup.sale.try_claim_purchased_tokens(&setup.participant),
+        Err(Ok(SaleError::NothingToClaim))
+    );
+
+    setup.sale.pause(&setup.admin, &PausableOperation::Withdraw);
+    assert_eq!(setup.sale.try_withdraw_raised_funds(&setup.admin), paused);
+    setup
+        .sale
+        .unpause(&setup.admin, &PausableOperation::Withdraw);
+    assert!(!setup.sale.is_paused(&PausableOperation::Withdraw));
+    setup.sale.withdraw_raised_funds(&setup.admin);
+    assert_eq!(
+        token::Client::new(&e, &setup.payment_token).balance(&setup.sale.address),
+        0
+    );
+}
+
+#[test]
+fn test_initialize_requires_deployer() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let config = SaleConfig {
+        sale_token: setup.sale_token.clone(),
+        payment_options: vec![
+            &e,
+            PaymentOption {
+                token: setup.payment_token.clone(),
+                rate: SwapRate {
+                    numerator: 2,
+                    denominator: 1,
+                },
+            },
+        ],
+        fund_recipient: setup.admin.clone(),
+        parameters: setup.sale.get_sales_parameters(),
+    };
+
+    //a contract deployed by someone else cannot be initialized by the admin
+    let salt = BytesN::from_array(&e, &[1; 32]);
+    let other = TokenSaleClient::new(&e, &e.register_contract(None, TokenSale));
+    assert_eq!(
+        other.try_initialize(&setup.admin, &salt, &config, &None, &None, &None),
+        Err(Ok(SaleError::Unauthorized))
+    );
+}
+
+#[test]
+fn test_cancel_failed_sale_and_refund() {
+    let e = Env::default();
+    let setup = create_custom_sale(&e, |_, _, args| {
+        args.config.parameters.soft_cap = 500_0000000;
+    });
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    let sale_token = token::Client::new(&e, &setup.sale_token);
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    assert_eq!(
+        setup.sale.try_cancel_sale(&setup.participant),
+        Err(Ok(SaleError::Unauthorized))
+    );
+
+    //the 200 tokens sold missed the soft cap, so the ended sale can still be cancelled
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.cancel_sale(&setup.admin);
+    assert_eq!(setup.sale.get_sale_status(), SaleStatus::Cancelled);
+    assert_eq!(sale_token.balance(&setup.sale.address), 0);
+    assert_eq!(
+        setup.sale.try_cancel_sale(&setup.admin),
+        Err(Ok(SaleError::SaleCancelled))
+    );
+    assert_eq!(
+        setup.sale.try_claim_purchased_tokens(&setup.participant),
+        Err(Ok(SaleError::SaleCancelled))
+    );
+
+    setup.sale.claim_refund(&setup.participant);
+    assert_eq!(payment_token.balance(&setup.participant), 1_000_0000000);
+    assert_eq!(payment_token.balance(&setup.sale.address), 0);
+}
+
+#[test]
+fn test_cancel_succeeded_sale() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+
+    //once ended above the soft cap, the participants are owed their tokens
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    assert_eq!(
+        setup.sale.try_cancel_sale(&setup.admin),
+        Err(Ok(SaleError::SaleSucceeded))
+    );
+    setup.sale.finalize_sale();
+    assert_eq!(
+        setup.sale.try_cancel_sale(&setup.admin),
+        Err(Ok(SaleError::InvalidStatusTransition))
+    );
+    setup.sale.claim_purchased_tokens(&setup.participant);
+    assert_eq!(
+        setup.sale.get_claimed_amount(&setup.participant),
+        200_0000000
+    );
+}
+
+#[test]
+fn test_partial_fill_fixed_once_started() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    assert_eq!(
+        setup.sale.try_set_partial_fill(&setup.admin, &true),
+        Err(Ok(SaleError::SaleAlreadyStarted))
+    );
+}
+
+#[test]
+fn test_sale_token_not_accepted_as_payment() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    assert_eq!(
+        setup
+            .sale
+            .try_set_payment_token(&setup.admin, &setup.sale_token),
+        Err(Ok(SaleError::InvalidPaymentOptions))
+    );
+}
+
+//A sale of the 7 decimals token paid only with a mock token of the given decimals, 1_000 of
+//which are minted to the participant. Contributions crossing the hard cap are partially
+//filled
+fn create_decimals_sale(
+    e: &Env,
+    decimals: u32,
+    rate: SwapRate,
+    hard_cap: u64,
+) -> (TestSale<'_>, MockTokenClient<'_>) {
+    let payment_token = MockTokenClient::new(e, &e.register_contract(None, MockToken));
+    let setup = create_custom_sale(e, |_, _, args| {
+        payment_token.set_decimals(&decimals);
+        args.config.payment_options = vec![
+            e,
+            PaymentOption {
+                token: payment_token.address.clone(),
+                rate,
+            },
+        ];
+        args.config.parameters.hard_cap = hard_cap;
+    });
+    payment_token.mint(&setup.participant, &(1_000 * 10i128.pow(decimals)));
+
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_500);
+    setup.sale.set_partial_fill(&setup.admin, &true);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+    (setup, payment_token)
+}
+
+#[test]
+fn test_six_decimals_payment_token() {
+    let e = Env::default();
+    //0.035 payment tokens per sale token, with a hard cap of 100 tokens and one base unit
+    let (setup, payment_token) = create_decimals_sale(
+        &e,
+        6,
+        SwapRate {
+            numerator: 1_000,
+            denominator: 35,
+        },
+        100_0000001,
+    );
+
+    //one base unit of the payment token is worth 285.71 base units of the sale token
+    let quote =
+        setup
+            .sale
+            .preview_contribute(&setup.participant, &payment_token.address, &1, &None, &None);
+    assert_eq!(quote.tokens_purchased, 285);
+
+    //10 payment tokens would buy 285.71 tokens, only the 3.5 paying for 100 are taken
+    setup.sale.contribute(
+        &setup.participant,
+        &payment_token.address,
+        &10_000000,
+        &None,
+        &None,
+    );
+    assert_eq!(payment_token.balance(&setup.sale.address), 3_500000);
+    //the extra base unit of the hard cap cannot be paid for and stays unsold
+    assert_eq!(setup.sale.get_total_sold(), 100_0000000);
+}
+
+#[test]
+fn test_eighteen_decimals_payment_token() {
+    let e = Env::default();
+    //3 sale tokens per payment token, with a hard cap of 100 tokens
+    let (setup, payment_token) = create_decimals_sale(
+        &e,
+        18,
+        SwapRate {
+            numerator: 3,
+            denominator: 1,
+        },
+        100_0000000,
+    );
+
+    //below 10^11 base units of the payment token nothing is bought, above it rounds down
+    for (amount, tokens) in [
+        (33_333_333_333, 0),
+        (33_333_333_334, 1),
+        (66_666_666_666, 1),
+    ] {
+        let quote = setup.sale.preview_contribute(
+            &setup.participant,
+            &payment_token.address,
+            &amount,
+            &None,
+            &None,
+        );
+        assert_eq!(quote.tokens_purchased, tokens);
+    }
+
+    //the 33.33 payment tokens paying for the hard cap buy 99.9999999 tokens, never more
+    setup.sale.contribute(
+        &setup.participant,
+        &payment_token.address,
+        &(50 * 10i128.pow(18)),
+        &None,
+        &None,
+    );
+    assert_eq!(
+        payment_token.balance(&setup.sale.address),
+        33_333_333_333_333_333_333
+    );
+    assert_eq!(setup.sale.get_total_sold(), 99_9999999);
+}
+
+#[test]
+fn test_contribute_with_min_out() {
+    let e = Env::default();
+    let setup = create_custom_sale(&e, |_, _, args| {
+        args.config.parameters.hard_cap = 100_0000000;
+    });
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_500);
+    setup.sale.set_partial_fill(&setup.admin, &true);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    let contribute = |min_tokens_out: i128, deadline: u64| {
+        setup.sale.try_contribute_with_min_out(
+            &setup.participant,
+            &setup.payment_token,
+            &100_0000000,
+            &min_tokens_out,
+            &deadline,
+            &None,
+            &None,
+        )
+    };
+
+    assert_eq!(contribute(0, 2_999), Err(Ok(SaleError::DeadlinePassed)));
+
+    //the 200 tokens asked for are partially filled up to the hard cap of 100
+    assert_eq!(
+        contribute(100_0000001, 3_000),
+        Err(Ok(SaleError::SlippageExceeded))
+    );
+    assert_eq!(payment_token.balance(&setup.participant), 1_000_0000000);
+
+    assert_eq!(contribute(100_0000000, 3_000), Ok(Ok(100_0000000)));
+    assert_eq!(payment_token.balance(&setup.participant), 950_0000000);
+    assert_eq!(
+        setup
+            .sale
+            .get_participant_total_purchase(&setup.participant),
+        100_0000000
+    );
+}
+
+#[test]
+fn test_halted_release_refund() {
+    let e = Env::default();
+    let setup = create_custom_sale(&e, |_, _, args| {
+        args.config.parameters.vesting.tge_unlock_bps = 5_000;
+        args.config.parameters.vesting.cliff_duration = 100_000;
+        args.config.parameters.vesting.vesting_duration = 100_000;
+        args.release = Some(ReleaseSchedule {
+            tranches: vec![
+                &e,
+                ReleaseTranche {
+                    release_time: 10_000,
+                    release_bps: 4_000,
+                },
+                ReleaseTranche {
+                    release_time: 20_000,
+                    release_bps: 6_000,
+                },
+            ],
+            halt_quorum_bps: 6_000,
+        });
+    });
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    let other = Address::generate(&e);
+    token::StellarAssetClient::new(&e, &setup.payment_token).mint(&other, &100_0000000);
+    for participant in [&setup.participant, &other] {
+        setup.sale.contribute(
+            participant,
+            &setup.payment_token,
+            &100_0000000,
+            &None,
+            &None,
+        );
+    }
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+
+    //the other participant claims the half of its 200 tokens unlocked at TGE before the halt
+    setup.sale.claim_purchased_tokens(&other);
+    assert_eq!(setup.sale.get_claimed_amount(&other), 100_0000000);
+
+    //only buyers have a say, half of the tokens sold is below the quorum, all of them reach it
+    assert_eq!(
+        setup.sale.try_vote_halt(&setup.admin),
+        Err(Ok(SaleError::NoVotingWeight))
+    );
+    setup.sale.vote_halt(&setup.participant);
+    assert_eq!(
+        setup.sale.try_claim_halt_refund(&setup.participant),
+        Err(Ok(SaleError::ReleaseNotHalted))
+    );
+    setup.sale.vote_halt(&other);
+    assert_eq!(setup.sale.get_halt_votes(), 400_0000000);
+    assert_eq!(
+        setup.sale.try_vote_halt(&other),
+        Err(Ok(SaleError::HaltVoteClosed))
+    );
+
+    //the second tranche never comes
+    e.ledger().with_mut(|ledger| ledger.timestamp = 20_000);
+    assert_eq!(setup.sale.get_released_bps(), 4_000);
+
+    //120 of the 200 raised are unreleased. The participant gives back 120 of its 200
+    //tokens for 60, the other could only give back 100 and gets 60 * 100 / 120
+    setup.sale.claim_halt_refund(&setup.participant);
+    setup.sale.claim_halt_refund(&other);
+    assert_eq!(payment_token.balance(&setup.participant), 960_0000000);
+    assert_eq!(payment_token.balance(&other), 50_0000000);
+    assert_eq!(
+        setup.sale.try_claim_halt_refund(&setup.participant),
+        Err(Ok(SaleError::AlreadyRefunded))
+    );
+
+    //the allocations are cut to the kept tokens, so nothing is paid twice
+    assert_eq!(
+        setup.sale.get_participant_allocation(&setup.participant),
+        80_0000000
+    );
+    assert_eq!(setup.sale.get_participant_allocation(&other), 100_0000000);
+    setup.sale.claim_purchased_tokens(&setup.participant);
+    assert_eq!(
+        setup.sale.get_claimed_amount(&setup.participant),
+        40_0000000
+    );
+    assert_eq!(
+        setup.sale.try_claim_purchased_tokens(&other),
+        Err(Ok(SaleError::NothingToClaim))
+    );
+
+    //the 120 and 100 tokens given back went to the project wallet, the contract only holds
+    //the 40 still vesting for the participant
+    let sale_token = token::Client::new(&e, &setup.sale_token);
+    assert_eq!(sale_token.balance(&setup.sale.address), 40_0000000);
+
+    //the recipient keeps the released 80
+    setup.sale.withdraw_raised_funds(&setup.admin);
+    assert_eq!(payment_token.balance(&setup.admin), 80_0000000);
+    assert_eq!(
+        payment_token.balance(&setup.sale.address),
+        200_0000000 - 60_0000000 - 50_0000000 - 80_0000000
+    );
+}
+
+#[test]
+fn test_opt_out() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let treasury = Address::generate(&e);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_500);
+    setup.sale.set_opt_out_config(
+        &setup.admin,
+        &Some(OptOutConfig {
+            deadline: 5_000,
+            penalty_bps: 1_000,
+            treasury: treasury.clone(),
+        }),
+    );
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+
+    let other = Address::generate(&e);
+    token::StellarAssetClient::new(&e, &setup.payment_token).mint(&other, &50_0000000);
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    setup
+        .sale
+        .contribute(&other, &setup.payment_token, &50_0000000, &None, &None);
+    let participants_count = || e.as_contract(&setup.sale.address, || read_participants_count(&e));
+    assert_eq!(participants_count(), 2);
+
+    //10% of the 100 paid is kept as a penalty and the 200 tokens go back to the sale
+    setup.sale.opt_out(&setup.participant, &setup.payment_token);
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    assert_eq!(payment_token.balance(&setup.participant), 990_0000000);
+    assert_eq!(payment_token.balance(&treasury), 10_0000000);
+    assert_eq!(
+        setup
+            .sale
+            .get_participant_total_purchase(&setup.participant),
+        0
+    );
+    assert_eq!(setup.sale.get_total_sold(), 100_0000000);
+    assert_eq!(
+        setup.sale.get_total_contribution(&setup.payment_token),
+        50_0000000
+    );
+    assert_eq!(participants_count(), 1);
+    assert_eq!(
+        setup
+            .sale
+            .try_opt_out(&setup.participant, &setup.payment_token),
+        Err(Ok(SaleError::NothingToClaim))
+    );
+
+    e.ledger().with_mut(|ledger| ledger.timestamp = 5_001);
+    assert_eq!(
+        setup.sale.try_opt_out(&other, &setup.payment_token),
+        Err(Ok(SaleError::OptOutClosed))
+    );
+}
+
+#[test]
+fn test_oversubscribed_settlement() {
+    let e = Env::default();
+    let setup = create_custom_sale(&e, |_, _, args| {
+        args.config.parameters.hard_cap = 333_3333333;
+    });
+    e.ledger().with_mut(|ledger| ledger.timestamp = 1_500);
+    setup.sale.set_oversubscription(&setup.admin, &true);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 3_000);
+
+    //600 tokens are bought for a hard cap of 333.3333333
+    let other = Address::generate(&e);
+    token::StellarAssetClient::new(&e, &setup.payment_token).mint(&other, &200_0000000);
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000000,
+        &None,
+        &None,
+    );
+    setup
+        .sale
+        .contribute(&other, &setup.payment_token, &200_0000000, &None, &None);
+    assert_eq!(setup.sale.get_total_sold(), 600_0000000);
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+
+    //allocations are rounded down and never exceed the hard cap
+    assert_eq!(
+        setup.sale.get_participant_allocation(&setup.participant),
+        111_1111111
+    );
+    assert_eq!(setup.sale.get_participant_allocation(&other), 222_2222222);
+
+    //the unfilled parts are refunded rounded down, the used parts being rounded up
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    setup.sale.claim(&setup.participant);
+    setup.sale.claim(&other);
+    assert_eq!(payment_token.balance(&setup.participant), 944_4444444);
+    assert_eq!(payment_token.balance(&other), 88_8888889);
+    setup.sale.withdraw_raised_funds(&setup.admin);
+    assert_eq!(payment_token.balance(&setup.admin), 166_6666666);
+
+    //the refunds and the withdrawal leave 1 unit of dust out of the 300 paid
+    assert_eq!(
+        44_4444444 + 88_8888889 + 166_6666666 + payment_token.balance(&setup.sale.address),
+        300_0000000
+    );
+    assert_eq!(payment_token.balance(&setup.sale.address), 1);
+
+    let sale_token = token::Client::new(&e, &setup.sale_token);
+    assert_eq!(sale_token.balance(&setup.participant), 111_1111111);
+    assert_eq!(sale_token.balance(&other), 222_2222222);
+    assert_eq!(sale_token.balance(&setup.sale.address), 0);
+}
+
+#[test]
+fn test_merkle_proofs() {
+    let e = Env::default();
+    let participants: std::vec::Vec<Address> = (0..5).map(|_| Address::generate(&e)).collect();
+    let mut leaves = Vec::new(&e);
+    for (index, participant) in participants.iter().enumerate() {
+        leaves.push_back(merkle::leaf_hash(
+            &e,
+            participant,
+            index as i128 * 100_0000000,
+        ));
+    }
+
+    //with 5 leaves the last one is carried up unchanged until it has a sibling
+    let root = merkle::build_root(&e, &leaves);
+    for (index, participant) in participants.iter().enumerate() {
+        let proof = merkle::build_proof(&e, &leaves, index as u32);
+        let leaf = merkle::leaf_hash(&e, participant, index as i128 * 100_0000000);
+        assert!(merkle::verify_proof(&e, leaf, &proof, &root));
+
+        let inflated = merkle::leaf_hash(&e, participant, index as i128 * 100_0000000 + 1);
+        assert!(!merkle::verify_proof(&e, inflated, &proof, &root));
+    }
+    assert_eq!(merkle::build_proof(&e, &leaves, 4).len(), 1);
+
+    //a proof only works for its own leaf
+    let proof = merkle::build_proof(&e, &leaves, 1);
+    assert!(!merkle::verify_proof(
+        &e,
+        leaves.get_unchecked(0),
+        &proof,
+        &root
+    ));
+    assert!(!merkle::verify_proof(
+        &e,
+        leaves.get_unchecked(2),
+        &proof,
+        &root
+    ));
+
+    //a single leaf is its own root
+    let single = vec![&e, leaves.get_unchecked(0)];
+    assert_eq!(merkle::build_root(&e, &single), leaves.get_unchecked(0));
+    assert!(merkle::build_proof(&e, &single, 0).is_empty());
+}
+
+#[test]
+fn test_whitelisted_contribution() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let other = Address::generate(&e);
+    let leaves = vec![
+        &e,
+        merkle::leaf_hash(&e, &setup.participant, 100_0000000),
+        merkle::leaf_hash(&e, &other, 0),
+    ];
+    setup
+        .sale
+        .set_merkle_root(&setup.admin, &Some(merkle::build_root(&e, &leaves)));
+
+    let proof = WhitelistProof {
+        max_allocation: 100_0000000,
+        proof: merkle::build_proof(&e, &leaves, 0),
+    };
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &10_0000000,
+            &None,
+            &None
+        ),
+        Err(Ok(SaleError::NotWhitelisted))
+    );
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &10_0000000,
+            &Some(WhitelistProof {
+                max_allocation: 0,
+                proof: proof.proof.clone(),
+            }),
+            &None
+        ),
+        Err(Ok(SaleError::NotWhitelisted))
+    );
+
+    //the 100 tokens of the leaf are bought with 50 payment tokens
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &50_0000000,
+        &Some(proof.clone()),
+        &None,
+    );
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &1,
+            &Some(proof),
+            &None
+        ),
+        Err(Ok(SaleError::AllocationExceeded))
+    );
+}
+
+//Voucher for the sale signed with the KYC key, as the compliance backend would
+fn sign_voucher(
+    e: &Env,
+    sale: &Address,
+    key: &SigningKey,
+    voucher: AllocationVoucher,
+) -> SignedVoucher {
+    let mut message = sale.clone().to_xdr(e);
+    message.append(&voucher.clone().to_xdr(e));
+    let message: std::vec::Vec<u8> = message.iter().collect();
+    SignedVoucher {
+        voucher,
+        signature: BytesN::from_array(e, &key.sign(&message).to_bytes()),
+    }
+}
+
+#[test]
+fn test_kyc_voucher() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let key = SigningKey::from_bytes(&[7; 32]);
+    setup.sale.set_kyc_signer(
+        &setup.admin,
+        &Some(BytesN::from_array(&e, &key.verifying_key().to_bytes())),
+    );
+    let voucher = |participant: &Address, expiry: u64, nonce: u64| {
+        sign_voucher(
+            &e,
+            &setup.sale.address,
+            &key,
+            AllocationVoucher {
+                participant: participant.clone(),
+                max_allocation: 0,
+                expiry,
+                nonce,
+            },
+        )
+    };
+    let contribute = |voucher: SignedVoucher| {
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &10_0000000,
+            &None,
+            &Some(voucher),
+        )
+    };
+
+    assert_eq!(
+        setup.sale.try_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &10_0000000,
+            &None,
+            &None
+        ),
+        Err(Ok(SaleError::NotWhitelisted))
+    );
+    assert_eq!(
+        contribute(voucher(&setup.admin, 5_000, 1)),
+        Err(Ok(SaleError::InvalidVoucher))
+    );
+    assert_eq!(
+        contribute(voucher(&setup.participant, 2_999, 1)),
+        Err(Ok(SaleError::VoucherExpired))
+    );
+
+    assert_eq!(
+        contribute(voucher(&setup.participant, 3_000, 1)),
+        Ok(Ok(()))
+    );
+    assert_eq!(
+        contribute(voucher(&setup.participant, 5_000, 1)),
+        Err(Ok(SaleError::VoucherAlreadyUsed))
+    );
+    assert_eq!(
+        contribute(voucher(&setup.participant, 5_000, 2)),
+        Ok(Ok(()))
+    );
+    assert_eq!(
+        setup
+            .sale
+            .get_participant_total_purchase(&setup.participant),
+        40_0000000
+    );
+}
+
+#[test]
+#[should_panic]
+fn test_kyc_voucher_bad_signature() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let key = SigningKey::from_bytes(&[7; 32]);
+    setup.sale.set_kyc_signer(
+        &setup.admin,
+        &Some(BytesN::from_array(&e, &key.verifying_key().to_bytes())),
+    );
+
+    //signed by another key, the signature check traps
+    let voucher = sign_voucher(
+        &e,
+        &setup.sale.address,
+        &SigningKey::from_bytes(&[8; 32]),
+        AllocationVoucher {
+            participant: setup.participant.clone(),
+            max_allocation: 0,
+            expiry: 5_000,
+            nonce: 1,
+        },
+    );
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &10_0000000,
+        &None,
+        &Some(voucher),
+    );
+}
+
+#[test]
+fn test_preview_gated_contribution() {
+    let e = Env::default();
+    let setup = create_sale(&e, None);
+    let leaves = vec![&e, merkle::leaf_hash(&e, &setup.participant, 100_0000000)];
+    setup
+        .sale
+        .set_merkle_root(&setup.admin, &Some(merkle::build_root(&e, &leaves)));
+    let proof = WhitelistProof {
+        max_allocation: 100_0000000,
+        proof: merkle::build_proof(&e, &leaves, 0),
+    };
+    let preview = |amount: i128, proof: Option<WhitelistProof>, voucher: Option<SignedVoucher>| {
+        setup.sale.preview_contribute(
+            &setup.participant,
+            &setup.payment_token,
+            &amount,
+            &proof,
+            &voucher,
+        )
+    };
+
+    //the same errors as contribute, the allocation of the proven leaf included
+    assert_eq!(
+        preview(50_0000000, None, None).error_code,
+        SaleError::NotWhitelisted as u32
+    );
+    assert_eq!(
+        preview(60_0000000, Some(proof.clone()), None).error_code,
+        SaleError::AllocationExceeded as u32
+    );
+    let quote = preview(50_0000000, Some(proof), None);
+    assert_eq!(quote.error_code, 0);
+    assert_eq!(quote.tokens_purchased, 100_0000000);
+
+    //previewing with a voucher leaves its nonce to the contribution
+    let key = SigningKey::from_bytes(&[7; 32]);
+    setup.sale.set_kyc_signer(
+        &setup.admin,
+        &Some(BytesN::from_array(&e, &key.verifying_key().to_bytes())),
+    );
+    let voucher = sign_voucher(
+        &e,
+        &setup.sale.address,
+        &key,
+        AllocationVoucher {
+            participant: setup.participant.clone(),
+            max_allocation: 20_0000000,
+            expiry: 5_000,
+            nonce: 1,
+        },
+    );
+    assert_eq!(
+        preview(20_0000000, None, Some(voucher.clone())).error_code,
+        SaleError::AllocationExceeded as u32
+    );
+    assert_eq!(
+        preview(10_0000000, None, Some(voucher.clone())).error_code,
+        0
+    );
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &10_0000000,
+        &None,
+        &Some(voucher.clone()),
+    );
+    assert_eq!(
+        preview(10_0000000, None, Some(voucher)).error_code,
+        SaleError::VoucherAlreadyUsed as u32
+    );
+}
+
+#[test]
+fn test_split_withdrawal_with_platform_fee() {
+    let e = Env::default();
+    let treasury = Address::generate(&e);
+    let setup = create_custom_sale(&e, |_, _, args| {
+        args.platform_fee = Some(PlatformFee {
+            treasury: treasury.clone(),
+            fee_bps: 250,
+            sale_token_fee_bps: 0,
+        });
+    });
+    let recipients: std::vec::Vec<Address> = (0..3).map(|_| Address::generate(&e)).collect();
+    let mut fund_recipients = Vec::new(&e);
+    for (recipient, share_bps) in recipients.iter().zip([6_001, 2_999, 1_000]) {
+        fund_recipients.push_back(FundRecipient {
+            recipient: recipient.clone(),
+            share_bps,
+        });
+    }
+    setup
+        .sale
+        .set_fund_recipients(&setup.admin, &fund_recipients);
+
+    setup.sale.contribute(
+        &setup.participant,
+        &setup.payment_token,
+        &100_0000001,
+        &None,
+        &None,
+    );
+    e.ledger().with_mut(|ledger| ledger.timestamp = 10_001);
+    setup.sale.finalize_sale();
+    for recipient in recipients.iter() {
+        setup.sale.withdraw_raised_funds(recipient);
+    }
+
+    //2.5% of the 100.0000001 raised is the fee, the 97.5000001 left are split rounding
+    //down and the first recipient gets the unit of dust
+    let payment_token = token::Client::new(&e, &setup.payment_token);
+    assert_eq!(payment_token.balance(&treasury), 2_5000000);
+    assert_eq!(
+        setup.sale.get_fee_collected(&setup.payment_token),
+        2_5000000
+    );
+    assert_eq!(payment_token.balance(&recipients[0]), 58_5097501);
+    assert_eq!(payment_token.balance(&recipients[1]), 29_2402500);
+    assert_eq!(payment_token.balance(&recipients[2]), 9_7500000);
+    assert_eq!(payment_token.balance(&setup.sale.address), 0);
+    assert_eq!(
+        setup.sale.try_withdraw_raised_funds(&recipients[0]),
+        Err(Ok(SaleError::FundsAlreadyWithdrawn))
+    );
+}
+
+#[test]
+fn test_vested_amount() {
+    let e = Env::default();
+    let mut parameters = SalesParameter {
+        start_time: 0,
+        end_time: 500,
+        soft_cap: 0,
+        hard_cap: 1_000_0000000,
+        min_buy: 0,
+        max_buy: 1_000_0000000,
+        tge_time: 1_000,
+        vesting: VestingSchedule {
+            tge_unlock_bps: 2_000,
+            cliff_duration: 500,
+            vesting_duration: 1_000,
+            step_interval: 0,
+        },
+    };
+    let vested_at = |parameters: &SalesParameter, timestamp: u64| {
+        e.ledger().with_mut(|ledger| ledger.timestamp = timestamp);
+        vesting::vested_amount(&e, parameters, 1_000_0000000)
+    };
+
+    //20% at TGE, then the other 80% linearly over 1_000 seconds after a 500 seconds cliff
+    assert_eq!(vested_at(&parameters, 999), 0);
+    assert_eq!(vested_at(&parameters, 1_000), 200_0000000);
+    assert_eq!(vested_at(&parameters, 1_499), 200_0000000);
+    assert_eq!(vested_at(&parameters, 1_500), 200_0000000);
+    assert_eq!(vested_at(&parameters, 2_000), 600_0000000);
+    assert_eq!(vested_at(&parameters, 2_500), 1_000_0000000);
+    assert_eq!(vested_at(&parameters, 10_000), 1_000_0000000);
+
+    //with steps of 300 seconds the 500 seconds elapsed only count as 300
+    parameters.vesting.step_interval = 300;
+    assert_eq!(vested_at(&parameters, 2_000), 440_0000000);
+    assert_eq!(vested_at(&parameters, 2_499), 920_0000000);
+    assert_eq!(vested_at(&parameters, 2_500), 1_000_0000000);
+}
@@ -0,0 +1,41 @@
+use soroban_sdk::{Address, Env};
+
+use crate::balances::{read_participant_allocation, read_participant_claimed_amount};
+use crate::sale_details::read_sales_parameters;
+use crate::storage_types::SalesParameter;
+
+pub(crate) const MAX_BPS: u32 = 10_000;
+
+//Amount of `total_purchased` released at the current ledger time: the TGE unlock at
+//tge_time, then after the cliff the rest vests linearly (per step when an interval is set)
+pub fn vested_amount(e: &Env, parameters: &SalesParameter, total_purchased: i128) -> i128 {
+    let now = e.ledger().timestamp();
+    if now < parameters.tge_time {
+        return 0;
+    }
+
+    let vesting = &parameters.vesting;
+    let tge_amount = total_purchased * vesting.tge_unlock_bps as i128 / MAX_BPS as i128;
+    let vesting_start = parameters.tge_time + vesting.cliff_duration;
+    if now < vesting_start {
+        return tge_amount;
+    }
+
+    let mut elapsed = now - vesting_start;
+    if elapsed >= vesting.vesting_duration {
+        return total_purchased;
+    }
+    if vesting.step_interval > 0 {
+        elapsed -= elapsed % vesting.step_interval;
+    }
+
+    let locked_amount = total_purchased - tge_amount;
+    tge_amount + locked_amount * elapsed as i128 / vesting.vesting_duration as i128
+}
+
+pub fn read_claimable_amount(e: &Env, participant: Address) -> i128 {
+    let parameters = read_sales_parameters(e);
+    let allocation = read_participant_allocation(e, participant.clone());
+    let claimed = read_participant_claimed_amount(e, participant);
+    (vested_amount(e, &parameters, allocation) - claimed).max(0)
+}
@@ -0,0 +1,58 @@
+use soroban_sdk::{Address, BytesN, Env};
+
+use crate::errors::SaleError;
+use crate::kyc::{check_voucher, read_kyc_signer};
+use crate::merkle::{leaf_hash, verify_proof};
+use crate::storage_types::{DataKey, SignedVoucher, WhitelistProof};
+
+pub fn read_merkle_root(e: &Env) -> Option<BytesN<32>> {
+    let key = DataKey::MerkleRoot;
+    e.storage().instance().get(&key)
+}
+
+pub fn write_merkle_root(e: &Env, root: Option<BytesN<32>>) {
+    let key = DataKey::MerkleRoot;
+    match root {
+        Some(root) => e.storage().instance().set(&key, &root),
+        None => e.storage().instance().remove(&key),
+    }
+}
+
+//Returns the participant's max allocation from the proven leaf, 0 when uncapped
+pub fn check_whitelisted(
+    e: &Env,
+    root: &BytesN<32>,
+    participant: &Address,
+    whitelist_proof: Option<WhitelistProof>,
+) -> Result<i128, SaleError> {
+    let whitelist_proof = whitelist_proof.ok_or(SaleError::NotWhitelisted)?;
+    let leaf = leaf_hash(e, participant, whitelist_proof.max_allocation);
+    if !verify_proof(e, leaf, &whitelist_proof.proof, root) {
+        return Err(SaleError::NotWhitelisted);
+    }
+    Ok(whitelist_proof.max_allocation)
+}
+
+//A voucher from the KYC signer is checked first, then the merkle proof. A sale with
+//neither a signer nor a root is open to anyone. Nothing is written, returns the max
+//allocation, 0 when uncapped, and the nonce of the voucher accepted
+pub fn check_eligibility(
+    e: &Env,
+    participant: &Address,
+    whitelist_proof: Option<WhitelistProof>,
+    voucher: Option<SignedVoucher>,
+) -> Result<(i128, Option<u64>), SaleError> {
+    let signer = read_kyc_signer(e);
+    if let (Some(signer), Some(voucher)) = (&signer, voucher) {
+        let nonce = voucher.voucher.nonce;
+        let max_allocation = check_voucher(e, signer, participant, voucher)?;
+        return Ok((max_allocation, Some(nonce)));
+    }
+
+    let max_allocation = match read_merkle_root(e) {
+        Some(root) => check_whitelisted(e, &root, participant, whitelist_proof)?,
+        None if signer.is_some() => return Err(SaleError::NotWhitelisted),
+        None => 0,
+    };
+    Ok((max_allocation, None))
+}